@@ -0,0 +1,89 @@
+//! The cluster the CLI talks to, behind a trait so commands can run against
+//! an RPC node or an in-process test validator alike.
+
+use anchor_lang::Discriminator;
+use marketplace_client::Listing;
+use solana_client::{
+    client_error::ClientError,
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use crate::error::{Error, Result};
+
+pub trait Chain {
+    fn account(&mut self, address: &Pubkey) -> Result<Option<Account>>;
+
+    /// Every listing account of the program, in any layout version.
+    fn listing_accounts(&mut self) -> Result<Vec<(Pubkey, Account)>>;
+
+    /// Sends `instructions` in one transaction paid for and signed by
+    /// `signer`, waiting for confirmation.
+    fn send(&mut self, instructions: &[Instruction], signer: &Keypair) -> Result<Signature>;
+}
+
+impl Chain for RpcClient {
+    fn account(&mut self, address: &Pubkey) -> Result<Option<Account>> {
+        let response = self
+            .get_account_with_commitment(address, self.commitment())
+            .map_err(client_error)?;
+        Ok(response.value)
+    }
+
+    fn listing_accounts(&mut self) -> Result<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(listing_filters()),
+            account_config: RpcAccountInfoConfig {
+                commitment: Some(self.commitment()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        self.get_program_accounts_with_config(&marketplace_client::ID, config)
+            .map_err(client_error)
+    }
+
+    fn send(&mut self, instructions: &[Instruction], signer: &Keypair) -> Result<Signature> {
+        let blockhash = self.get_latest_blockhash().map_err(client_error)?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signer.pubkey()),
+            &[signer],
+            blockhash,
+        );
+        self.send_and_confirm_transaction(&transaction)
+            .map_err(client_error)
+    }
+}
+
+/// Filters selecting the program's listing accounts. They match on the
+/// discriminator rather than the size so listings still in an older layout
+/// are found too.
+pub fn listing_filters() -> Vec<RpcFilterType> {
+    vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+        0,
+        Listing::DISCRIMINATOR.to_vec(),
+    ))]
+}
+
+/// Connects to `url` with confirmed commitment.
+pub fn connect(url: &str) -> RpcClient {
+    RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed())
+}
+
+fn client_error(error: ClientError) -> Error {
+    match error.get_transaction_error() {
+        Some(transaction_error) => transaction_error.into(),
+        None => Error::other(error),
+    }
+}
@@ -0,0 +1,384 @@
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use marketplace_client::{
+    args, instructions, pda, state, Category, Listing, ListingMetadata, PaymentKind, RoyaltyConfig,
+    SellerProfile,
+};
+use solana_sdk::{
+    account::{from_account, Account},
+    clock::Clock,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    sysvar,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+use crate::{
+    chain::Chain,
+    error::{Error, Result},
+    output::{ListingView, Output},
+    Command,
+};
+
+/// Carries out `command` with `wallet` as the signing seller or buyer.
+pub fn run(chain: &mut impl Chain, wallet: &Keypair, command: Command) -> Result<Output> {
+    match command {
+        Command::Create {
+            mint,
+            price,
+            quantity,
+            name,
+            payment_mint,
+            token_account,
+            swap,
+            description,
+            uri,
+        } => {
+            let seller = wallet.pubkey();
+            let token_program = owner(chain, &mint)?;
+            let token_account = token_account.unwrap_or_else(|| {
+                get_associated_token_address_with_program_id(&seller, &mint, &token_program)
+            });
+            let nonce = match chain.account(&pda::seller_profile(&seller))? {
+                Some(account) => state::deserialize::<SellerProfile>(&account.data)?.listing_nonce,
+                None => 0,
+            };
+
+            let metadata = ListingMetadata {
+                description,
+                uri,
+                category: Category::Other,
+                tags: vec![],
+            };
+            let ix = match payment_mint {
+                Some(payment_mint) if swap => instructions::create_swap_listing(
+                    &seller,
+                    nonce,
+                    &token_account,
+                    &mint,
+                    &payment_mint,
+                    &token_program,
+                    args::CreateSwapListing {
+                        price,
+                        quantity,
+                        name,
+                        dutch_auction: None,
+                        expires_at: None,
+                        buyer_restriction: None,
+                        max_per_buyer: None,
+                        metadata,
+                    },
+                ),
+                _ => instructions::create_listing(
+                    &seller,
+                    nonce,
+                    &token_account,
+                    &mint,
+                    payment_mint.as_ref(),
+                    &token_program,
+                    args::CreateListing {
+                        price,
+                        quantity,
+                        name,
+                        dutch_auction: None,
+                        expires_at: None,
+                        buyer_restriction: None,
+                        max_per_buyer: None,
+                        metadata,
+                    },
+                ),
+            };
+            let signature = chain.send(&[ix], wallet)?;
+            Ok(Output::Created {
+                listing: pda::listing(&seller, nonce),
+                signature,
+            })
+        }
+        Command::List { seller, all } => {
+            let now = now(chain)?;
+            let accounts = match seller {
+                Some(seller) => seller_listings(chain, &seller)?,
+                None => chain.listing_accounts()?,
+            };
+
+            // One account that fails to decode shouldn't hide every other listing
+            let mut listings = vec![];
+            let mut skipped = vec![];
+            for (address, account) in accounts {
+                match view(address, &account, now) {
+                    Ok(view) => listings.push(view),
+                    Err(error) => skipped.push((address, error)),
+                }
+            }
+            if !all {
+                listings.retain(|view| view.status() == "active");
+            }
+            listings.sort_by_key(|view| (view.listing.seller, view.listing.nonce));
+            Ok(Output::Listings { listings, skipped })
+        }
+        Command::Inspect { listing } => {
+            let now = now(chain)?;
+            let account = listing_account(chain, &listing)?;
+            Ok(Output::Listing(Box::new(view(listing, &account, now)?)))
+        }
+        Command::Purchase {
+            listing: address,
+            quantity,
+            max_price,
+            payment_account,
+        } => {
+            let buyer = wallet.pubkey();
+            let now = now(chain)?;
+            let listing = state::listing(&listing_account(chain, &address)?.data)?;
+            let max_price = match max_price {
+                Some(max_price) => max_price,
+                None => listing.current_price(now)?,
+            };
+            let max_total = max_price
+                .checked_mul(quantity)
+                .ok_or_else(|| Error::other("total price overflows"))?;
+
+            let item_mint = escrowed_mint(chain, &address)?;
+            let token_program = owner(chain, &item_mint)?;
+            let buyer_token_account =
+                get_associated_token_address_with_program_id(&buyer, &item_mint, &token_program);
+            let mut ixs = vec![create_associated_token_account_idempotent(
+                &buyer,
+                &buyer,
+                &item_mint,
+                &token_program,
+            )];
+
+            let payment = match listing.payment_kind {
+                PaymentKind::Spl | PaymentKind::Swap => {
+                    let payment_mint = listing.payment_mint;
+                    let payment_token_program = owner(chain, &payment_mint)?;
+                    let seller_payment_account = get_associated_token_address_with_program_id(
+                        &listing.seller,
+                        &payment_mint,
+                        &payment_token_program,
+                    );
+                    ixs.push(create_associated_token_account_idempotent(
+                        &buyer,
+                        &listing.seller,
+                        &payment_mint,
+                        &payment_token_program,
+                    ));
+                    Some(instructions::SplPayment {
+                        buyer_payment_account: payment_account.unwrap_or_else(|| {
+                            get_associated_token_address_with_program_id(
+                                &buyer,
+                                &payment_mint,
+                                &payment_token_program,
+                            )
+                        }),
+                        seller_payment_account,
+                        token_program: payment_token_program,
+                    })
+                }
+                PaymentKind::Native => None,
+            };
+
+            match (listing.payment_kind, &payment) {
+                // Swaps pay the seller directly, without royalties
+                (PaymentKind::Swap, Some(payment)) => ixs.push(instructions::swap(
+                    &buyer,
+                    &address,
+                    &listing,
+                    &item_mint,
+                    &buyer_token_account,
+                    payment,
+                    &token_program,
+                    false,
+                    args::Swap {
+                        quantity,
+                        max_price,
+                        proof: vec![],
+                    },
+                )),
+                _ => {
+                    let creator_accounts = creator_accounts(
+                        chain,
+                        &buyer,
+                        &item_mint,
+                        &listing,
+                        payment.as_ref(),
+                        &mut ixs,
+                    )?;
+                    ixs.push(instructions::purchase(
+                        &buyer,
+                        &address,
+                        &listing,
+                        &item_mint,
+                        &buyer_token_account,
+                        &token_program,
+                        payment.as_ref(),
+                        false,
+                        &creator_accounts,
+                        args::Purchase {
+                            quantity,
+                            max_price,
+                            max_total,
+                            proof: vec![],
+                        },
+                    ));
+                }
+            }
+
+            let signature = chain.send(&ixs, wallet)?;
+            Ok(Output::Purchased {
+                listing: address,
+                quantity,
+                max_price,
+                signature,
+            })
+        }
+        Command::Cancel {
+            listing: address,
+            token_account,
+        } => {
+            let seller = wallet.pubkey();
+            let listing = state::listing(&listing_account(chain, &address)?.data)?;
+            if listing.seller != seller {
+                return Err(Error::other(format!(
+                    "listing {address} belongs to {}, not the wallet {seller}",
+                    listing.seller
+                )));
+            }
+
+            let item_mint = escrowed_mint(chain, &address)?;
+            let token_program = owner(chain, &item_mint)?;
+            let mut ixs = vec![];
+            let token_account = match token_account {
+                Some(token_account) => token_account,
+                None => {
+                    ixs.push(create_associated_token_account_idempotent(
+                        &seller,
+                        &seller,
+                        &item_mint,
+                        &token_program,
+                    ));
+                    get_associated_token_address_with_program_id(
+                        &seller,
+                        &item_mint,
+                        &token_program,
+                    )
+                }
+            };
+            ixs.push(instructions::cancel_listing(
+                &seller,
+                &address,
+                &token_account,
+                &item_mint,
+                &token_program,
+            ));
+
+            let signature = chain.send(&ixs, wallet)?;
+            Ok(Output::Cancelled {
+                listing: address,
+                signature,
+            })
+        }
+    }
+}
+
+fn now(chain: &mut impl Chain) -> Result<i64> {
+    let account = chain
+        .account(&sysvar::clock::ID)?
+        .ok_or_else(|| Error::other("clock sysvar not found"))?;
+    let clock: Clock =
+        from_account(&account).ok_or_else(|| Error::other("invalid clock sysvar"))?;
+    Ok(clock.unix_timestamp)
+}
+
+/// The program owning `address`, e.g. the token program of a mint.
+fn owner(chain: &mut impl Chain, address: &Pubkey) -> Result<Pubkey> {
+    chain
+        .account(address)?
+        .map(|account| account.owner)
+        .ok_or_else(|| Error::other(format!("account {address} not found")))
+}
+
+fn listing_account(chain: &mut impl Chain, address: &Pubkey) -> Result<Account> {
+    chain
+        .account(address)?
+        .ok_or_else(|| Error::other(format!("listing {address} not found")))
+}
+
+/// Listings don't store their item mint, so read it off the escrow.
+fn escrowed_mint(chain: &mut impl Chain, listing: &Pubkey) -> Result<Pubkey> {
+    let escrow = pda::escrow(listing);
+    let account = chain
+        .account(&escrow)?
+        .ok_or_else(|| Error::other(format!("escrow {escrow} not found")))?;
+    // Both token programs start accounts with the mint
+    account
+        .data
+        .get(..32)
+        .map(|mint| Pubkey::try_from(mint).expect("slice is 32 bytes"))
+        .ok_or_else(|| Error::other(format!("escrow {escrow} is not a token account")))
+}
+
+/// The seller's listings, found by walking their listing nonces.
+fn seller_listings(chain: &mut impl Chain, seller: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+    let listing_nonce = match chain.account(&pda::seller_profile(seller))? {
+        Some(account) => state::deserialize::<SellerProfile>(&account.data)?.listing_nonce,
+        None => 0,
+    };
+
+    let mut listings = vec![];
+    for nonce in 0..listing_nonce {
+        let address = pda::listing(seller, nonce);
+        if let Some(account) = chain.account(&address)? {
+            listings.push((address, account));
+        }
+    }
+    Ok(listings)
+}
+
+fn view(address: Pubkey, account: &Account, now: i64) -> Result<ListingView> {
+    Ok(ListingView {
+        address,
+        listing: state::listing(&account.data)?,
+        legacy: state::is_legacy_listing(&account.data),
+        now,
+    })
+}
+
+/// Accounts creators of `item_mint` are paid royalties into, in royalty
+/// config order. Missing creator token accounts are created by the buyer.
+fn creator_accounts(
+    chain: &mut impl Chain,
+    buyer: &Pubkey,
+    item_mint: &Pubkey,
+    listing: &Listing,
+    payment: Option<&instructions::SplPayment>,
+    ixs: &mut Vec<Instruction>,
+) -> Result<Vec<Pubkey>> {
+    let royalty_config = match chain.account(&pda::royalty_config(item_mint))? {
+        Some(account) => state::deserialize::<RoyaltyConfig>(&account.data)?,
+        None => return Ok(vec![]),
+    };
+
+    Ok(royalty_config
+        .creators
+        .iter()
+        .map(|creator| match payment {
+            Some(payment) => {
+                ixs.push(create_associated_token_account_idempotent(
+                    buyer,
+                    &creator.address,
+                    &listing.payment_mint,
+                    &payment.token_program,
+                ));
+                get_associated_token_address_with_program_id(
+                    &creator.address,
+                    &listing.payment_mint,
+                    &payment.token_program,
+                )
+            }
+            None => creator.address,
+        })
+        .collect())
+}
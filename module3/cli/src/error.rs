@@ -0,0 +1,65 @@
+use std::fmt;
+
+use marketplace_client::{errors, ErrorCode};
+use serde_json::{json, Value};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+/// Why a command failed.
+#[derive(Debug)]
+pub enum Error {
+    /// The marketplace program rejected the transaction.
+    Program(ErrorCode),
+    /// The transaction failed for any other reason.
+    Transaction(TransactionError),
+    /// The command could not be carried out, e.g. a missing account or an
+    /// unreachable RPC node.
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn other(message: impl fmt::Display) -> Self {
+        Error::Other(message.to_string())
+    }
+
+    pub fn json(&self) -> Value {
+        match self {
+            Error::Program(error) => json!({
+                "code": u32::from(*error),
+                "name": error.name(),
+                "message": error.to_string(),
+            }),
+            other => json!({ "message": other.to_string() }),
+        }
+    }
+}
+
+impl From<TransactionError> for Error {
+    fn from(error: TransactionError) -> Self {
+        if let TransactionError::InstructionError(_, InstructionError::Custom(code)) = error {
+            if let Some(program_error) = errors::from_code(code) {
+                return Error::Program(program_error);
+            }
+        }
+        Error::Transaction(error)
+    }
+}
+
+impl From<anchor_lang::error::Error> for Error {
+    fn from(error: anchor_lang::error::Error) -> Self {
+        Error::other(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Program(error) => write!(f, "{error} ({})", error.name()),
+            Error::Transaction(error) => write!(f, "transaction failed: {error}"),
+            Error::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,136 @@
+//! Command-line tool for sellers and buyers on the marketplace.
+//!
+//! Commands are plain functions over a [`Chain`], so they run the same
+//! against an RPC node and an in-process test validator. Failed transactions
+//! are decoded back into the program's [`ErrorCode`](marketplace_client::ErrorCode)
+//! messages.
+
+pub mod chain;
+pub mod commands;
+pub mod error;
+pub mod output;
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use solana_sdk::pubkey::Pubkey;
+
+pub use chain::Chain;
+pub use commands::run;
+pub use error::{Error, Result};
+pub use output::Output;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "marketplace-cli",
+    version,
+    about = "Create, browse, buy from and cancel marketplace listings"
+)]
+pub struct Cli {
+    /// RPC URL or moniker: mainnet-beta, devnet, testnet or localhost
+    #[arg(short = 'u', long, global = true, default_value = "localhost")]
+    pub url: String,
+
+    /// Keypair of the wallet that signs and pays [default: ~/.config/solana/id.json]
+    #[arg(short = 'k', long, global = true)]
+    pub keypair: Option<PathBuf>,
+
+    #[arg(long, global = true, value_enum, default_value_t = Format::Human)]
+    pub output: Format,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// The RPC URL behind `--url`, expanding cluster monikers.
+    pub fn rpc_url(&self) -> &str {
+        match self.url.as_str() {
+            "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+            "devnet" | "d" => "https://api.devnet.solana.com",
+            "testnet" | "t" => "https://api.testnet.solana.com",
+            "localhost" | "l" => "http://localhost:8899",
+            url => url,
+        }
+    }
+
+    pub fn keypair_path(&self) -> PathBuf {
+        self.keypair.clone().unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".config/solana/id.json")
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List items from the wallet for sale
+    Create {
+        /// Mint of the items to sell
+        #[arg(long)]
+        mint: Pubkey,
+        /// Price per item, in base units of the payment mint or in lamports
+        #[arg(long)]
+        price: u64,
+        #[arg(long)]
+        quantity: u64,
+        #[arg(long)]
+        name: String,
+        /// Mint buyers pay in; listings without one are priced in lamports
+        #[arg(long)]
+        payment_mint: Option<Pubkey>,
+        /// Account to take the items from [default: the wallet's associated token account]
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+        /// Price the items in another item token, `--payment-mint`, paid straight to the wallet
+        #[arg(long, requires = "payment_mint")]
+        swap: bool,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[arg(long, default_value = "")]
+        uri: String,
+    },
+    /// Show active listings
+    List {
+        /// Only show this seller's listings
+        #[arg(long)]
+        seller: Option<Pubkey>,
+        /// Include listings that are sold out or no longer active
+        #[arg(long)]
+        all: bool,
+    },
+    /// Show everything about one listing
+    Inspect { listing: Pubkey },
+    /// Buy items from a listing with the wallet
+    Purchase {
+        listing: Pubkey,
+        #[arg(long, default_value_t = 1)]
+        quantity: u64,
+        /// Highest unit price to accept [default: the current price]
+        #[arg(long)]
+        max_price: Option<u64>,
+        /// Account to pay from for SPL-priced and swap listings [default: the wallet's associated token account]
+        #[arg(long)]
+        payment_account: Option<Pubkey>,
+    },
+    /// Cancel one of the wallet's listings, returning unsold items
+    Cancel {
+        listing: Pubkey,
+        /// Account to return the items to [default: the wallet's associated token account]
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+}
+
+impl Command {
+    /// Whether the command sends a transaction and so needs the keypair.
+    pub fn signs(&self) -> bool {
+        !matches!(self, Command::List { .. } | Command::Inspect { .. })
+    }
+}
@@ -0,0 +1,43 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use marketplace_cli::{chain, run, Cli, Error, Format};
+use solana_sdk::signature::{read_keypair_file, Keypair};
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = cli.output;
+
+    // Read-only commands never sign, so they work without a keypair file
+    let wallet = if cli.command.signs() {
+        read_keypair_file(cli.keypair_path()).map_err(|error| {
+            Error::other(format!(
+                "failed to read keypair {}: {error}",
+                cli.keypair_path().display()
+            ))
+        })
+    } else {
+        Ok(Keypair::new())
+    };
+    let result = wallet.and_then(|wallet| {
+        let mut client = chain::connect(cli.rpc_url());
+        run(&mut client, &wallet, cli.command)
+    });
+
+    match result {
+        Ok(output) => {
+            for warning in output.warnings() {
+                eprintln!("Warning: {warning}");
+            }
+            println!("{}", output.render(format));
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            match format {
+                Format::Human => eprintln!("Error: {error}"),
+                Format::Json => println!("{}", serde_json::json!({ "error": error.json() })),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt::Write;
+
+use marketplace_client::{Listing, PaymentKind};
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::{Error, Format};
+
+/// What a command did, printable for people or as JSON.
+pub enum Output {
+    Created {
+        listing: Pubkey,
+        signature: Signature,
+    },
+    Listings {
+        listings: Vec<ListingView>,
+        /// Accounts that could not be decoded as listings, and why.
+        skipped: Vec<(Pubkey, Error)>,
+    },
+    Listing(Box<ListingView>),
+    Purchased {
+        listing: Pubkey,
+        quantity: u64,
+        max_price: u64,
+        signature: Signature,
+    },
+    Cancelled {
+        listing: Pubkey,
+        signature: Signature,
+    },
+}
+
+/// A decoded listing and the clock time it was read at.
+pub struct ListingView {
+    pub address: Pubkey,
+    pub listing: Listing,
+    /// Whether the account still has to be migrated to the current layout.
+    pub legacy: bool,
+    pub now: i64,
+}
+
+impl Output {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Human => self.human(),
+            Format::Json => self.json().to_string(),
+        }
+    }
+
+    pub fn human(&self) -> String {
+        match self {
+            Output::Created { listing, signature } => {
+                format!("Created listing {listing}\nSignature: {signature}")
+            }
+            Output::Listings { listings, .. } if listings.is_empty() => {
+                "No listings found".to_string()
+            }
+            Output::Listings { listings, .. } => listings
+                .iter()
+                .map(|view| {
+                    format!(
+                        "{}  {} x{} @ {}  {}",
+                        view.address,
+                        view.listing.name,
+                        view.listing.quantity,
+                        view.price(),
+                        view.status()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Output::Listing(view) => view.human(),
+            Output::Purchased {
+                listing,
+                quantity,
+                signature,
+                ..
+            } => {
+                format!("Purchased {quantity} from listing {listing}\nSignature: {signature}")
+            }
+            Output::Cancelled { listing, signature } => {
+                format!("Cancelled listing {listing}\nSignature: {signature}")
+            }
+        }
+    }
+
+    /// Problems that did not stop the command, for printing to stderr.
+    pub fn warnings(&self) -> Vec<String> {
+        match self {
+            Output::Listings { skipped, .. } => skipped
+                .iter()
+                .map(|(address, error)| format!("skipped listing {address}: {error}"))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn json(&self) -> Value {
+        match self {
+            Output::Created { listing, signature } => json!({
+                "listing": listing.to_string(),
+                "signature": signature.to_string(),
+            }),
+            Output::Listings { listings, .. } => {
+                Value::Array(listings.iter().map(ListingView::json).collect())
+            }
+            Output::Listing(view) => view.json(),
+            Output::Purchased {
+                listing,
+                quantity,
+                max_price,
+                signature,
+            } => json!({
+                "listing": listing.to_string(),
+                "quantity": quantity,
+                "maxPrice": max_price,
+                "signature": signature.to_string(),
+            }),
+            Output::Cancelled { listing, signature } => json!({
+                "listing": listing.to_string(),
+                "signature": signature.to_string(),
+            }),
+        }
+    }
+}
+
+impl ListingView {
+    pub fn status(&self) -> &'static str {
+        if self.listing.is_expired(self.now) {
+            "expired"
+        } else if self.listing.active {
+            "active"
+        } else {
+            "inactive"
+        }
+    }
+
+    /// Unit price right now, falling back to the list price if it cannot be
+    /// computed.
+    pub fn current_price(&self) -> u64 {
+        self.listing
+            .current_price(self.now)
+            .unwrap_or(self.listing.price)
+    }
+
+    fn currency(&self) -> String {
+        match self.listing.payment_kind {
+            PaymentKind::Spl => self.listing.payment_mint.to_string(),
+            PaymentKind::Native => "lamports".to_string(),
+            PaymentKind::Swap => format!("{} (swap)", self.listing.payment_mint),
+        }
+    }
+
+    fn price(&self) -> String {
+        format!("{} {}", self.current_price(), self.currency())
+    }
+
+    fn human(&self) -> String {
+        let listing = &self.listing;
+        let mut out = String::new();
+        let _ = writeln!(out, "Listing:     {}", self.address);
+        let _ = writeln!(out, "Name:        {}", listing.name);
+        let _ = writeln!(out, "Seller:      {}", listing.seller);
+        let _ = writeln!(out, "Status:      {}", self.status());
+        let _ = writeln!(out, "Price:       {}", self.price());
+        if let Some(dutch_auction) = &listing.dutch_auction {
+            let _ = writeln!(
+                out,
+                "Dutch:       {} falling to {} over {}s from {}",
+                listing.price,
+                dutch_auction.floor_price,
+                dutch_auction.duration,
+                dutch_auction.start_time
+            );
+        }
+        let _ = writeln!(out, "Quantity:    {}", listing.quantity);
+        if let Some(expires_at) = listing.expires_at {
+            let _ = writeln!(out, "Expires at:  {expires_at}");
+        }
+        if let Some(max_per_buyer) = listing.max_per_buyer {
+            let _ = writeln!(out, "Per buyer:   {max_per_buyer}");
+        }
+        let _ = writeln!(out, "Category:    {:?}", listing.metadata.category);
+        if !listing.metadata.description.is_empty() {
+            let _ = writeln!(out, "Description: {}", listing.metadata.description);
+        }
+        if !listing.metadata.uri.is_empty() {
+            let _ = writeln!(out, "URI:         {}", listing.metadata.uri);
+        }
+        if !listing.metadata.tags.is_empty() {
+            let _ = writeln!(out, "Tags:        {}", listing.metadata.tags.join(", "));
+        }
+        let _ = write!(
+            out,
+            "Sales:       {} ({} units, {} volume)",
+            listing.sale_count, listing.units_sold, listing.total_volume
+        );
+        if self.legacy {
+            out.push_str("\nLayout:      legacy, run migrate_listing to upgrade");
+        }
+        out
+    }
+
+    fn json(&self) -> Value {
+        let listing = &self.listing;
+        json!({
+            "address": self.address.to_string(),
+            "name": listing.name,
+            "seller": listing.seller.to_string(),
+            "nonce": listing.nonce,
+            "status": self.status(),
+            "paymentMint": match listing.payment_kind {
+                PaymentKind::Spl | PaymentKind::Swap => Value::String(listing.payment_mint.to_string()),
+                PaymentKind::Native => Value::Null,
+            },
+            "swap": listing.payment_kind == PaymentKind::Swap,
+            "price": listing.price,
+            "currentPrice": self.current_price(),
+            "floorPrice": listing.dutch_auction.as_ref().map(|dutch_auction| dutch_auction.floor_price),
+            "quantity": listing.quantity,
+            "expiresAt": listing.expires_at,
+            "maxPerBuyer": listing.max_per_buyer,
+            "description": listing.metadata.description,
+            "uri": listing.metadata.uri,
+            "category": format!("{:?}", listing.metadata.category),
+            "tags": listing.metadata.tags,
+            "saleCount": listing.sale_count,
+            "unitsSold": listing.units_sold,
+            "totalVolume": listing.total_volume,
+            "version": if self.legacy { 0 } else { listing.version },
+        })
+    }
+}
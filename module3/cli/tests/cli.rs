@@ -0,0 +1,277 @@
+mod common;
+
+use anchor_lang::Discriminator;
+use clap::Parser;
+use common::*;
+use marketplace::{ErrorCode, Listing};
+use marketplace_cli::{Cli, Command, Error, Format, Output};
+use marketplace_client::pda;
+use solana_sdk::{
+    account::Account, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signer::Signer,
+};
+
+#[test]
+fn parses_global_options_after_the_command() {
+    let cli = Cli::try_parse_from([
+        "marketplace-cli",
+        "list",
+        "--all",
+        "--output",
+        "json",
+        "-u",
+        "devnet",
+    ])
+    .unwrap();
+
+    assert_eq!(cli.output, Format::Json);
+    assert_eq!(cli.rpc_url(), "https://api.devnet.solana.com");
+    assert!(matches!(
+        cli.command,
+        Command::List {
+            seller: None,
+            all: true
+        }
+    ));
+}
+
+#[test]
+fn creates_and_inspects_a_listing() {
+    let mut f = Fixture::new();
+
+    let listing = f.create();
+
+    let state: Listing = f.account(listing);
+    assert_eq!(listing, pda::listing(&f.seller.pubkey(), 0));
+    assert_eq!(state.metadata.description, "A test item");
+    assert_eq!(f.token_balance(pda::escrow(&listing)), ITEM_QUANTITY);
+
+    let output = f.buyer(Command::Inspect { listing }).unwrap();
+    let json = output.json();
+    assert_eq!(json["name"], ITEM_NAME);
+    assert_eq!(json["status"], "active");
+    assert_eq!(json["currentPrice"], ITEM_PRICE);
+    assert_eq!(json["paymentMint"], f.payment_mint.to_string());
+    assert!(output
+        .render(Format::Human)
+        .contains("Description: A test item"));
+}
+
+#[test]
+fn lists_active_listings() {
+    let mut f = Fixture::new();
+    let sold_out = f.create();
+    let listing = f.create();
+    f.buyer(Command::Purchase {
+        listing: sold_out,
+        quantity: ITEM_QUANTITY,
+        max_price: None,
+        payment_account: Some(f.buyer_payment_account),
+    })
+    .unwrap();
+
+    let seller = f.seller.pubkey();
+    let active = f
+        .buyer(Command::List {
+            seller: Some(seller),
+            all: false,
+        })
+        .unwrap()
+        .json();
+    assert_eq!(active.as_array().unwrap().len(), 1);
+    assert_eq!(active[0]["address"], listing.to_string());
+
+    let everything = f
+        .buyer(Command::List {
+            seller: None,
+            all: true,
+        })
+        .unwrap();
+    let json = everything.json();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert_eq!(json[0]["address"], sold_out.to_string());
+    assert_eq!(json[0]["status"], "inactive");
+    assert!(everything.human().contains(ITEM_NAME));
+    // The listing filter kept the config and seller profile out
+    assert!(everything.warnings().is_empty());
+}
+
+#[test]
+fn skips_listings_that_fail_to_decode() {
+    let mut f = Fixture::new();
+    let listing = f.create();
+    // Carries the listing discriminator but not a listing's data
+    let broken = Pubkey::new_unique();
+    let mut data = Listing::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&[0; 4]);
+    f.set_account(
+        broken,
+        Account {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: marketplace_client::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let output = f
+        .buyer(Command::List {
+            seller: None,
+            all: true,
+        })
+        .unwrap();
+
+    let json = output.json();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["address"], listing.to_string());
+    let warnings = output.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with(&format!("skipped listing {broken}: ")));
+}
+
+#[test]
+fn purchases_into_associated_token_accounts() {
+    let mut f = Fixture::new();
+    let listing = f.create();
+
+    f.buyer(Command::Purchase {
+        listing,
+        quantity: 2,
+        max_price: None,
+        payment_account: Some(f.buyer_payment_account),
+    })
+    .unwrap();
+
+    let total = ITEM_PRICE * 2;
+    let fee = total * FEE_BPS as u64 / 10_000;
+    assert_eq!(f.token_balance(ata(&f.buyer.pubkey(), &f.item_mint)), 2);
+    assert_eq!(
+        f.token_balance(ata(&f.seller.pubkey(), &f.payment_mint)),
+        total - fee
+    );
+    assert_eq!(
+        f.token_balance(f.buyer_payment_account),
+        STARTING_TOKENS - total
+    );
+}
+
+#[test]
+fn swaps_for_a_swap_listing() {
+    let mut f = Fixture::new();
+    let command = Command::Create {
+        mint: f.item_mint,
+        price: ITEM_PRICE,
+        quantity: ITEM_QUANTITY,
+        name: ITEM_NAME.to_string(),
+        payment_mint: Some(f.payment_mint),
+        token_account: Some(f.seller_item_account),
+        swap: true,
+        description: String::new(),
+        uri: String::new(),
+    };
+    let listing = match f.seller(command).unwrap() {
+        Output::Created { listing, .. } => listing,
+        _ => panic!("expected a created listing"),
+    };
+    assert_eq!(
+        f.buyer(Command::Inspect { listing }).unwrap().json()["swap"],
+        true
+    );
+
+    f.buyer(Command::Purchase {
+        listing,
+        quantity: 2,
+        max_price: None,
+        payment_account: Some(f.buyer_payment_account),
+    })
+    .unwrap();
+
+    // Swaps pay the seller in full, with no protocol fee
+    let total = ITEM_PRICE * 2;
+    assert_eq!(f.token_balance(ata(&f.buyer.pubkey(), &f.item_mint)), 2);
+    assert_eq!(
+        f.token_balance(ata(&f.seller.pubkey(), &f.payment_mint)),
+        total
+    );
+}
+
+#[test]
+fn cancels_a_listing() {
+    let mut f = Fixture::new();
+    let listing = f.create();
+
+    let output = f
+        .seller(Command::Cancel {
+            listing,
+            token_account: None,
+        })
+        .unwrap();
+
+    assert!(matches!(output, Output::Cancelled { .. }));
+    assert_eq!(
+        f.token_balance(ata(&f.seller.pubkey(), &f.item_mint)),
+        ITEM_QUANTITY
+    );
+}
+
+#[test]
+fn refuses_to_cancel_someone_elses_listing() {
+    let mut f = Fixture::new();
+    let listing = f.create();
+
+    let error = f
+        .buyer(Command::Cancel {
+            listing,
+            token_account: None,
+        })
+        .err()
+        .unwrap();
+
+    assert!(matches!(error, Error::Other(_)));
+}
+
+#[test]
+fn reports_program_errors_by_message() {
+    let mut f = Fixture::new();
+    let listing = f.create();
+
+    let error = f
+        .buyer(Command::Purchase {
+            listing,
+            quantity: 1,
+            max_price: Some(ITEM_PRICE - 1),
+            payment_account: Some(f.buyer_payment_account),
+        })
+        .err()
+        .unwrap();
+
+    assert!(matches!(error, Error::Program(ErrorCode::PriceMismatch)));
+    assert_eq!(error.json()["code"], u32::from(ErrorCode::PriceMismatch));
+    assert!(error
+        .to_string()
+        .starts_with(&ErrorCode::PriceMismatch.to_string()));
+}
+
+#[test]
+fn reports_a_paused_marketplace() {
+    let mut f = Fixture::new();
+    f.set_paused(true);
+
+    let command = Command::Create {
+        mint: f.item_mint,
+        price: ITEM_PRICE,
+        quantity: 1,
+        name: ITEM_NAME.to_string(),
+        payment_mint: None,
+        token_account: Some(f.seller_item_account),
+        swap: false,
+        description: String::new(),
+        uri: String::new(),
+    };
+    let error = f.seller(command).err().unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        "Marketplace is paused (MarketplacePaused)"
+    );
+}
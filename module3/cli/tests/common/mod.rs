@@ -0,0 +1,321 @@
+//! In-process validator fixture for the CLI tests, built with the client's
+//! instruction builders the same way the CLI itself talks to the program.
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token::spl_token;
+use marketplace_cli::{chain, chain::Chain, run, Command, Error, Output, Result};
+use marketplace_client::{args, instructions, pda};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+    transaction::Transaction,
+};
+use tokio::runtime::Runtime;
+
+pub const FEE_BPS: u16 = 250;
+pub const ITEM_PRICE: u64 = 100;
+pub const ITEM_QUANTITY: u64 = 5;
+pub const ITEM_NAME: &str = "Test Item";
+
+/// Items and payment tokens each user starts with.
+pub const STARTING_TOKENS: u64 = 1_000_000;
+
+/// A started test validator with an initialized config and fee vault, plus a
+/// seller holding items and a buyer holding payment tokens.
+pub struct Fixture {
+    pub runtime: Runtime,
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub seller: Keypair,
+    pub buyer: Keypair,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub seller_item_account: Pubkey,
+    pub buyer_payment_account: Pubkey,
+    /// Every account a transaction has touched, which stands in for the
+    /// program account index an RPC node keeps.
+    pub known_accounts: Vec<Pubkey>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let runtime = Runtime::new().unwrap();
+        let mut program_test = ProgramTest::new(
+            "marketplace",
+            marketplace_client::ID,
+            processor!(marketplace::entry),
+        );
+        program_test.prefer_bpf(false);
+        let context = runtime.block_on(program_test.start_with_context());
+
+        let mut f = Self {
+            runtime,
+            context,
+            admin: Keypair::new(),
+            seller: Keypair::new(),
+            buyer: Keypair::new(),
+            item_mint: Pubkey::default(),
+            payment_mint: Pubkey::default(),
+            seller_item_account: Pubkey::default(),
+            buyer_payment_account: Pubkey::default(),
+            known_accounts: vec![],
+        };
+        let payer = f.context.payer.pubkey();
+        let users = [f.admin.pubkey(), f.seller.pubkey(), f.buyer.pubkey()];
+        let funding: Vec<_> = users
+            .iter()
+            .map(|user| system_instruction::transfer(&payer, user, 10 * LAMPORTS_PER_SOL))
+            .collect();
+        f.process(&funding, &[]).unwrap();
+
+        f.item_mint = f.create_mint();
+        f.payment_mint = f.create_mint();
+        f.seller_item_account = f.create_token_account(f.item_mint, f.seller.pubkey());
+        f.buyer_payment_account = f.create_token_account(f.payment_mint, f.buyer.pubkey());
+        f.mint_to(f.item_mint, f.seller_item_account, STARTING_TOKENS);
+        f.mint_to(f.payment_mint, f.buyer_payment_account, STARTING_TOKENS);
+
+        let admin = f.admin.insecure_clone();
+        let ixs = [
+            instructions::initialize_config(
+                &admin.pubkey(),
+                args::InitializeConfig { fee_bps: FEE_BPS },
+            ),
+            instructions::initialize_fee_vault(&admin.pubkey(), &f.payment_mint, &spl_token::ID),
+        ];
+        f.process(&ixs, &[&admin]).unwrap();
+        f.known_accounts
+            .extend([pda::config(), pda::fee_vault(&f.payment_mint)]);
+        f
+    }
+
+    pub fn run_as(&mut self, wallet: &Keypair, command: Command) -> Result<Output> {
+        let mut chain = TestChain {
+            runtime: &self.runtime,
+            context: &mut self.context,
+            known_accounts: &mut self.known_accounts,
+        };
+        run(&mut chain, wallet, command)
+    }
+
+    pub fn seller(&mut self, command: Command) -> Result<Output> {
+        let seller = self.seller.insecure_clone();
+        self.run_as(&seller, command)
+    }
+
+    pub fn buyer(&mut self, command: Command) -> Result<Output> {
+        let buyer = self.buyer.insecure_clone();
+        self.run_as(&buyer, command)
+    }
+
+    /// Lists `ITEM_QUANTITY` items at `ITEM_PRICE` payment tokens each.
+    pub fn create(&mut self) -> Pubkey {
+        let command = Command::Create {
+            mint: self.item_mint,
+            price: ITEM_PRICE,
+            quantity: ITEM_QUANTITY,
+            name: ITEM_NAME.to_string(),
+            payment_mint: Some(self.payment_mint),
+            token_account: Some(self.seller_item_account),
+            swap: false,
+            description: "A test item".to_string(),
+            uri: String::new(),
+        };
+        match self.seller(command).unwrap() {
+            Output::Created { listing, .. } => listing,
+            _ => panic!("expected a created listing"),
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        let admin = self.admin.insecure_clone();
+        let ix = instructions::set_paused(&admin.pubkey(), args::SetPaused { paused });
+        self.process(&[ix], &[&admin]).unwrap();
+    }
+
+    /// Stores `account` at `address`, where the test chain will find it.
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.context
+            .set_account(&address, &AccountSharedData::from(account));
+        self.known_accounts.push(address);
+    }
+
+    pub fn token_balance(&mut self, address: Pubkey) -> u64 {
+        let account = self.get_account(address).expect("token account not found");
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount
+    }
+
+    pub fn account<T: AccountDeserialize>(&mut self, address: Pubkey) -> T {
+        let account = self.get_account(address).expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    fn get_account(&mut self, address: Pubkey) -> Option<Account> {
+        self.runtime
+            .block_on(self.context.banks_client.get_account(address))
+            .unwrap()
+    }
+
+    /// Signs `instructions` with the context payer and `signers`.
+    fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            self.context.last_blockhash,
+        );
+        self.runtime
+            .block_on(self.context.banks_client.process_transaction(transaction))
+    }
+
+    fn create_mint(&mut self) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        let rent = self
+            .runtime
+            .block_on(self.context.banks_client.get_rent())
+            .unwrap();
+        let ixs = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &payer,
+                None,
+                0,
+            )
+            .unwrap(),
+        ];
+        self.process(&ixs, &[&mint]).unwrap();
+        mint.pubkey()
+    }
+
+    fn create_token_account(&mut self, mint: Pubkey, owner: Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self
+            .runtime
+            .block_on(self.context.banks_client.get_rent())
+            .unwrap();
+        let ixs = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::ID,
+                &account.pubkey(),
+                &mint,
+                &owner,
+            )
+            .unwrap(),
+        ];
+        self.process(&ixs, &[&account]).unwrap();
+        account.pubkey()
+    }
+
+    fn mint_to(&mut self, mint: Pubkey, account: Pubkey, amount: u64) {
+        let ix = spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &mint,
+            &account,
+            &self.context.payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        self.process(&[ix], &[]).unwrap();
+    }
+}
+
+/// Drives the in-process validator from the CLI's synchronous commands.
+struct TestChain<'a> {
+    runtime: &'a Runtime,
+    context: &'a mut ProgramTestContext,
+    known_accounts: &'a mut Vec<Pubkey>,
+}
+
+impl Chain for TestChain<'_> {
+    fn account(&mut self, address: &Pubkey) -> Result<Option<Account>> {
+        self.runtime
+            .block_on(self.context.banks_client.get_account(*address))
+            .map_err(Error::other)
+    }
+
+    /// The banks client cannot scan program accounts, so apply the CLI's
+    /// own RPC filters to every program account a transaction has touched.
+    fn listing_accounts(&mut self) -> Result<Vec<(Pubkey, Account)>> {
+        let filters = chain::listing_filters();
+        let mut accounts = vec![];
+        for address in self.known_accounts.clone() {
+            let account = match self.account(&address)? {
+                Some(account) if account.owner == marketplace_client::ID => account,
+                _ => continue,
+            };
+            let shared = AccountSharedData::from(account.clone());
+            if filters.iter().all(|filter| filter.allows(&shared)) {
+                accounts.push((address, account));
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn send(&mut self, instructions: &[Instruction], signer: &Keypair) -> Result<Signature> {
+        for ix in instructions {
+            for meta in &ix.accounts {
+                if !self.known_accounts.contains(&meta.pubkey) {
+                    self.known_accounts.push(meta.pubkey);
+                }
+            }
+        }
+
+        let banks_client = &mut self.context.banks_client;
+        let blockhash = self
+            .runtime
+            .block_on(banks_client.get_latest_blockhash())
+            .map_err(Error::other)?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signer.pubkey()),
+            &[signer],
+            blockhash,
+        );
+        let signature = transaction.signatures[0];
+        match self
+            .runtime
+            .block_on(banks_client.process_transaction(transaction))
+        {
+            Ok(()) => Ok(signature),
+            Err(BanksClientError::TransactionError(error))
+            | Err(BanksClientError::SimulationError { err: error, .. }) => Err(error.into()),
+            Err(error) => Err(Error::other(error)),
+        }
+    }
+}
+
+pub fn ata(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    anchor_spl::associated_token::get_associated_token_address(wallet, mint)
+}
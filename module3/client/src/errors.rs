@@ -0,0 +1,11 @@
+//! Decoding of the program's custom error codes.
+
+use marketplace::ErrorCode;
+use num_traits::FromPrimitive;
+
+/// The program error behind a `Custom` instruction error code, if it is one.
+/// Its `Display` impl gives the `#[msg]` text.
+pub fn from_code(code: u32) -> Option<ErrorCode> {
+    let index = code.checked_sub(anchor_lang::error::ERROR_CODE_OFFSET)?;
+    ErrorCode::from_u32(index)
+}
@@ -0,0 +1,758 @@
+//! One builder per program instruction.
+//!
+//! Builders take the wallets, mints and token accounts involved, followed by
+//! the instruction's arguments as the program's own [`args`](crate::args)
+//! struct when it has any. Program addresses are derived with [`pda`], and
+//! optional accounts the program treats as absent are passed as the program
+//! id, as Anchor expects.
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey, Result},
+    solana_program::{instruction::Instruction, system_program, sysvar},
+    InstructionData, ToAccountMetas,
+};
+use marketplace::{
+    accounts, instruction as args, Auction, BatchPurchase, ErrorCode, Listing, Offer, PaymentKind,
+};
+
+use crate::pda;
+
+fn build(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+/// Appends creator payment accounts, in royalty config order, as writable
+/// remaining accounts.
+fn with_creators(mut ix: Instruction, creator_accounts: &[Pubkey]) -> Instruction {
+    ix.accounts.extend(
+        creator_accounts
+            .iter()
+            .map(|account| AccountMeta::new(*account, false)),
+    );
+    ix
+}
+
+/// SPL token accounts for paying for an SPL-priced listing.
+#[derive(Clone, Copy, Debug)]
+pub struct SplPayment {
+    pub buyer_payment_account: Pubkey,
+    pub seller_payment_account: Pubkey,
+    /// Token program that owns the payment mint.
+    pub token_program: Pubkey,
+}
+
+/// Lists items from `seller_token_account` under the seller's next listing
+/// `nonce` (see [`SellerProfile`](crate::SellerProfile), 0 for a new seller).
+/// Listings without a `payment_mint` are priced in lamports.
+pub fn create_listing(
+    seller: &Pubkey,
+    nonce: u64,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    payment_mint: Option<&Pubkey>,
+    token_program: &Pubkey,
+    args: args::CreateListing,
+) -> Instruction {
+    let listing = pda::listing(seller, nonce);
+    build(
+        accounts::CreateListing {
+            seller: *seller,
+            seller_profile: pda::seller_profile(seller),
+            config: pda::config(),
+            listing,
+            seller_token_account: *seller_token_account,
+            escrow_token_account: pda::escrow(&listing),
+            item_mint: *item_mint,
+            payment_mint: payment_mint.copied(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args,
+    )
+}
+
+/// Lists items from `seller_token_account` priced in `payment_mint`, another
+/// item token that buyers trade for them with [`swap`].
+pub fn create_swap_listing(
+    seller: &Pubkey,
+    nonce: u64,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+    args: args::CreateSwapListing,
+) -> Instruction {
+    let listing = pda::listing(seller, nonce);
+    build(
+        accounts::CreateListing {
+            seller: *seller,
+            seller_profile: pda::seller_profile(seller),
+            config: pda::config(),
+            listing,
+            seller_token_account: *seller_token_account,
+            escrow_token_account: pda::escrow(&listing),
+            item_mint: *item_mint,
+            payment_mint: Some(*payment_mint),
+            token_program: *token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args,
+    )
+}
+
+/// Buys from `listing`. Pass `payment` for SPL-priced listings and `None`
+/// for lamport-priced ones. A buyer receipt is included whenever the listing
+/// caps purchases per buyer, and a sale receipt when `record_sale` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn purchase(
+    buyer: &Pubkey,
+    listing_address: &Pubkey,
+    listing: &Listing,
+    item_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+    token_program: &Pubkey,
+    payment: Option<&SplPayment>,
+    record_sale: bool,
+    creator_accounts: &[Pubkey],
+    args: args::Purchase,
+) -> Instruction {
+    let spl = listing.payment_kind == PaymentKind::Spl;
+    let ix = build(
+        accounts::Purchase {
+            buyer: *buyer,
+            listing: *listing_address,
+            seller: listing.seller,
+            escrow_token_account: pda::escrow(listing_address),
+            buyer_token_account: *buyer_token_account,
+            buyer_payment_account: payment.map(|payment| payment.buyer_payment_account),
+            seller_payment_account: payment.map(|payment| payment.seller_payment_account),
+            item_mint: *item_mint,
+            payment_mint: spl.then_some(listing.payment_mint),
+            config: pda::config(),
+            fee_vault: spl.then(|| pda::fee_vault(&listing.payment_mint)),
+            royalty_config: pda::royalty_config(item_mint),
+            buyer_receipt: listing
+                .max_per_buyer
+                .map(|_| pda::buyer_receipt(listing_address, buyer)),
+            sale_receipt: record_sale
+                .then(|| pda::sale_receipt(listing_address, listing.sale_count)),
+            token_program: *token_program,
+            payment_token_program: payment.map(|payment| payment.token_program),
+            system_program: system_program::ID,
+        },
+        args,
+    );
+    with_creators(ix, creator_accounts)
+}
+
+/// Trades the buyer's `listing.payment_mint` tokens for items from a swap
+/// listing, paying the seller directly from and into the `payment` accounts,
+/// without fees or royalties. `token_program` owns the item mint. A buyer
+/// receipt is included whenever the listing caps purchases per buyer, and a
+/// sale receipt when `record_sale` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    buyer: &Pubkey,
+    listing_address: &Pubkey,
+    listing: &Listing,
+    item_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+    payment: &SplPayment,
+    token_program: &Pubkey,
+    record_sale: bool,
+    args: args::Swap,
+) -> Instruction {
+    build(
+        accounts::Swap {
+            buyer: *buyer,
+            listing: *listing_address,
+            escrow_token_account: pda::escrow(listing_address),
+            buyer_token_account: *buyer_token_account,
+            buyer_payment_account: payment.buyer_payment_account,
+            seller_payment_account: payment.seller_payment_account,
+            item_mint: *item_mint,
+            payment_mint: listing.payment_mint,
+            config: pda::config(),
+            buyer_receipt: listing
+                .max_per_buyer
+                .map(|_| pda::buyer_receipt(listing_address, buyer)),
+            sale_receipt: record_sale
+                .then(|| pda::sale_receipt(listing_address, listing.sale_count)),
+            token_program: *token_program,
+            payment_token_program: payment.token_program,
+            system_program: system_program::ID,
+        },
+        args,
+    )
+}
+
+/// Absent accounts are the program id, which must not be write-locked.
+fn writable_unless_absent(account: Pubkey) -> AccountMeta {
+    if account == marketplace::ID {
+        AccountMeta::new_readonly(account, false)
+    } else {
+        AccountMeta::new(account, false)
+    }
+}
+
+/// One listing to buy from with [`purchase_batch`].
+#[derive(Clone)]
+pub struct BatchItem<'a> {
+    pub listing_address: Pubkey,
+    pub listing: &'a Listing,
+    pub item_mint: Pubkey,
+    pub buyer_token_account: Pubkey,
+    /// Token program that owns the item mint.
+    pub token_program: Pubkey,
+    /// SPL token accounts for SPL-priced listings, `None` for lamports.
+    pub payment: Option<SplPayment>,
+    /// Payment accounts of the item's royalty creators, in config order.
+    pub creator_accounts: Vec<Pubkey>,
+    pub quantity: u64,
+    pub max_price: u64,
+}
+
+/// Buys from every listing in `items` in one instruction, laying out each
+/// listing's accounts as the program expects. Each item brings its own token
+/// programs, and a buyer receipt whenever its listing caps purchases per
+/// buyer. Fails with `InvalidBatchIndex` when a listing's accounts would
+/// start beyond remaining account 255, which its `u8` index cannot address.
+pub fn purchase_batch(buyer: &Pubkey, items: &[BatchItem]) -> Result<Instruction> {
+    let mut ix = build(
+        accounts::PurchaseBatch {
+            buyer: *buyer,
+            config: pda::config(),
+            system_program: system_program::ID,
+        },
+        args::PurchaseBatch { items: vec![] },
+    );
+
+    let mut purchases = Vec::with_capacity(items.len());
+    let mut remaining: Vec<AccountMeta> = vec![];
+    for item in items {
+        purchases.push(BatchPurchase {
+            listing_index: u8::try_from(remaining.len())
+                .map_err(|_| ErrorCode::InvalidBatchIndex)?,
+            quantity: item.quantity,
+            max_price: item.max_price,
+        });
+
+        // Lamport-priced listings pass the program id for their SPL accounts
+        let absent = marketplace::ID;
+        let (buyer_payment_account, seller_payment_account, payment_token_program) =
+            item.payment.map_or((absent, absent, absent), |payment| {
+                (
+                    payment.buyer_payment_account,
+                    payment.seller_payment_account,
+                    payment.token_program,
+                )
+            });
+        let (payment_mint, fee_vault) = match item.listing.payment_kind {
+            PaymentKind::Spl => (
+                item.listing.payment_mint,
+                pda::fee_vault(&item.listing.payment_mint),
+            ),
+            // Swap listings are refused by the program either way
+            PaymentKind::Native | PaymentKind::Swap => (absent, absent),
+        };
+        let buyer_receipt = item.listing.max_per_buyer.map_or(absent, |_| {
+            pda::buyer_receipt(&item.listing_address, buyer)
+        });
+        remaining.extend([
+            AccountMeta::new(item.listing_address, false),
+            AccountMeta::new(item.listing.seller, false),
+            AccountMeta::new(pda::escrow(&item.listing_address), false),
+            AccountMeta::new(item.buyer_token_account, false),
+            AccountMeta::new_readonly(item.item_mint, false),
+            AccountMeta::new_readonly(pda::royalty_config(&item.item_mint), false),
+            writable_unless_absent(buyer_payment_account),
+            writable_unless_absent(seller_payment_account),
+            AccountMeta::new_readonly(payment_mint, false),
+            writable_unless_absent(fee_vault),
+            writable_unless_absent(buyer_receipt),
+            AccountMeta::new_readonly(item.token_program, false),
+            AccountMeta::new_readonly(payment_token_program, false),
+        ]);
+        remaining.extend(
+            item.creator_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false)),
+        );
+    }
+
+    ix.accounts.extend(remaining);
+    ix.data = args::PurchaseBatch { items: purchases }.data();
+    Ok(ix)
+}
+
+/// Returns the listing's unsold items to the seller and closes it.
+pub fn cancel_listing(
+    seller: &Pubkey,
+    listing_address: &Pubkey,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::CancelListing {
+            seller: *seller,
+            listing: *listing_address,
+            escrow_token_account: pda::escrow(listing_address),
+            seller_token_account: *seller_token_account,
+            item_mint: *item_mint,
+            token_program: *token_program,
+        },
+        args::CancelListing {},
+    )
+}
+
+/// Closes an expired listing on the seller's behalf; anyone may crank it.
+pub fn expire_listing(
+    cranker: &Pubkey,
+    listing_address: &Pubkey,
+    listing: &Listing,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::ExpireListing {
+            cranker: *cranker,
+            seller: listing.seller,
+            listing: *listing_address,
+            escrow_token_account: pda::escrow(listing_address),
+            seller_token_account: *seller_token_account,
+            item_mint: *item_mint,
+            token_program: *token_program,
+        },
+        args::ExpireListing {},
+    )
+}
+
+/// Closes a sold-out listing and its escrow, sweeping anything sent to the
+/// escrow since into `seller_token_account`.
+pub fn close_listing(
+    seller: &Pubkey,
+    listing_address: &Pubkey,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::CloseListing {
+            seller: *seller,
+            listing: *listing_address,
+            escrow_token_account: pda::escrow(listing_address),
+            seller_token_account: *seller_token_account,
+            item_mint: *item_mint,
+            token_program: *token_program,
+        },
+        args::CloseListing {},
+    )
+}
+
+/// Changes a listing's price or metadata, or moves items in or out of escrow.
+pub fn update_listing(
+    seller: &Pubkey,
+    listing_address: &Pubkey,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    token_program: &Pubkey,
+    args: args::UpdateListing,
+) -> Instruction {
+    build(
+        accounts::UpdateListing {
+            seller: *seller,
+            listing: *listing_address,
+            escrow_token_account: pda::escrow(listing_address),
+            seller_token_account: *seller_token_account,
+            item_mint: *item_mint,
+            token_program: *token_program,
+        },
+        args,
+    )
+}
+
+/// Moves a listing in the original name-derived layout, and its escrowed
+/// items, to the seller's listing at `nonce`, priced in `payment_mint` (SOL
+/// when `None`). `token_program` owns the item mint.
+pub fn migrate_listing(
+    seller: &Pubkey,
+    nonce: u64,
+    legacy_listing: &Pubkey,
+    item_mint: &Pubkey,
+    payment_mint: Option<&Pubkey>,
+    token_program: &Pubkey,
+) -> Instruction {
+    let listing = pda::listing(seller, nonce);
+    build(
+        accounts::MigrateListing {
+            seller: *seller,
+            seller_profile: pda::seller_profile(seller),
+            legacy_listing: *legacy_listing,
+            legacy_escrow_account: pda::escrow(legacy_listing),
+            listing,
+            escrow_token_account: pda::escrow(&listing),
+            item_mint: *item_mint,
+            payment_mint: payment_mint.copied(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args::MigrateListing {},
+    )
+}
+
+/// Escrows the buyer's payment for an offer on `item_mint`. `token_program`
+/// owns the payment mint.
+pub fn create_offer(
+    buyer: &Pubkey,
+    buyer_payment_account: &Pubkey,
+    item_mint: &Pubkey,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+    args: args::CreateOffer,
+) -> Instruction {
+    let offer = pda::offer(buyer, item_mint);
+    build(
+        accounts::CreateOffer {
+            buyer: *buyer,
+            offer,
+            buyer_payment_account: *buyer_payment_account,
+            escrow_payment_account: pda::offer_escrow(&offer),
+            item_mint: *item_mint,
+            payment_mint: *payment_mint,
+            config: pda::config(),
+            token_program: *token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args,
+    )
+}
+
+/// Sells items into `offer`. `token_program` owns the item mint and
+/// `payment_token_program` the payment mint. The buyer's payment account is
+/// refunded what is left in escrow once the offer is filled.
+#[allow(clippy::too_many_arguments)]
+pub fn accept_offer(
+    seller: &Pubkey,
+    offer: &Offer,
+    seller_token_account: &Pubkey,
+    buyer_token_account: &Pubkey,
+    seller_payment_account: &Pubkey,
+    buyer_payment_account: &Pubkey,
+    token_program: &Pubkey,
+    payment_token_program: &Pubkey,
+    creator_accounts: &[Pubkey],
+    args: args::AcceptOffer,
+) -> Instruction {
+    let offer_address = pda::offer(&offer.buyer, &offer.item_mint);
+    let ix = build(
+        accounts::AcceptOffer {
+            seller: *seller,
+            buyer: offer.buyer,
+            offer: offer_address,
+            escrow_payment_account: pda::offer_escrow(&offer_address),
+            seller_token_account: *seller_token_account,
+            buyer_token_account: *buyer_token_account,
+            seller_payment_account: *seller_payment_account,
+            buyer_payment_account: *buyer_payment_account,
+            config: pda::config(),
+            fee_vault: pda::fee_vault(&offer.payment_mint),
+            royalty_config: pda::royalty_config(&offer.item_mint),
+            item_mint: offer.item_mint,
+            payment_mint: offer.payment_mint,
+            token_program: *token_program,
+            payment_token_program: *payment_token_program,
+        },
+        args,
+    );
+    with_creators(ix, creator_accounts)
+}
+
+/// Refunds what is left of the buyer's offer on `item_mint` and closes it.
+pub fn cancel_offer(
+    buyer: &Pubkey,
+    item_mint: &Pubkey,
+    buyer_payment_account: &Pubkey,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let offer = pda::offer(buyer, item_mint);
+    build(
+        accounts::CancelOffer {
+            buyer: *buyer,
+            offer,
+            escrow_payment_account: pda::offer_escrow(&offer),
+            buyer_payment_account: *buyer_payment_account,
+            payment_mint: *payment_mint,
+            token_program: *token_program,
+        },
+        args::CancelOffer {},
+    )
+}
+
+/// Creates the global config with `admin` as its admin.
+pub fn initialize_config(admin: &Pubkey, args: args::InitializeConfig) -> Instruction {
+    build(
+        accounts::InitializeConfig {
+            admin: *admin,
+            config: pda::config(),
+            system_program: system_program::ID,
+        },
+        args,
+    )
+}
+
+/// Changes the protocol fee and/or hands the config to a new admin. Signed
+/// by the current `admin`.
+pub fn update_config(admin: &Pubkey, args: args::UpdateConfig) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            admin: *admin,
+            config: pda::config(),
+        },
+        args,
+    )
+}
+
+/// Pauses or resumes creating listings, offers and auctions, buying and
+/// bidding. Cancelling, withdrawing and settling keep working while paused.
+/// Admin only.
+pub fn set_paused(admin: &Pubkey, args: args::SetPaused) -> Instruction {
+    build(
+        accounts::SetPaused {
+            admin: *admin,
+            config: pda::config(),
+        },
+        args,
+    )
+}
+
+/// Upgrades a config in an older layout to the current one, unpaused.
+pub fn migrate_config(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::MigrateConfig {
+            admin: *admin,
+            config: pda::config(),
+            system_program: system_program::ID,
+        },
+        args::MigrateConfig {},
+    )
+}
+
+/// Creates the fee vault that collects protocol fees in `payment_mint`.
+pub fn initialize_fee_vault(
+    admin: &Pubkey,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::InitializeFeeVault {
+            admin: *admin,
+            config: pda::config(),
+            fee_vault: pda::fee_vault(payment_mint),
+            payment_mint: *payment_mint,
+            token_program: *token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args::InitializeFeeVault {},
+    )
+}
+
+/// Drains the `payment_mint` fee vault into the admin's token account.
+pub fn withdraw_fees(
+    admin: &Pubkey,
+    payment_mint: &Pubkey,
+    admin_token_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::WithdrawFees {
+            admin: *admin,
+            config: pda::config(),
+            fee_vault: pda::fee_vault(payment_mint),
+            admin_token_account: *admin_token_account,
+            payment_mint: *payment_mint,
+            token_program: *token_program,
+        },
+        args::WithdrawFees {},
+    )
+}
+
+/// Withdraws lamport fees collected on the config to the admin.
+pub fn withdraw_native_fees(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::WithdrawNativeFees {
+            admin: *admin,
+            config: pda::config(),
+        },
+        args::WithdrawNativeFees {},
+    )
+}
+
+/// Registers creator royalties for `item_mint`; `authority` must be its mint
+/// authority.
+pub fn create_royalty_config(
+    authority: &Pubkey,
+    item_mint: &Pubkey,
+    args: args::CreateRoyaltyConfig,
+) -> Instruction {
+    build(
+        accounts::CreateRoyaltyConfig {
+            authority: *authority,
+            royalty_config: pda::royalty_config(item_mint),
+            item_mint: *item_mint,
+            system_program: system_program::ID,
+        },
+        args,
+    )
+}
+
+/// Replaces the creators and shares paid on sales of `item_mint`. Signed by
+/// the royalty config's `authority`.
+pub fn update_royalty_config(
+    authority: &Pubkey,
+    item_mint: &Pubkey,
+    args: args::UpdateRoyaltyConfig,
+) -> Instruction {
+    build(
+        accounts::UpdateRoyaltyConfig {
+            authority: *authority,
+            royalty_config: pda::royalty_config(item_mint),
+        },
+        args,
+    )
+}
+
+/// Starts an English auction named `args.name` under the seller's next
+/// auction `nonce` (see [`SellerProfile`](crate::SellerProfile), 0 for a new
+/// seller). `token_program` owns the item mint and `payment_token_program`
+/// the payment mint.
+#[allow(clippy::too_many_arguments)]
+pub fn create_auction(
+    seller: &Pubkey,
+    nonce: u64,
+    seller_token_account: &Pubkey,
+    item_mint: &Pubkey,
+    payment_mint: &Pubkey,
+    token_program: &Pubkey,
+    payment_token_program: &Pubkey,
+    args: args::CreateAuction,
+) -> Instruction {
+    let auction = pda::auction(seller, nonce);
+    build(
+        accounts::CreateAuction {
+            seller: *seller,
+            seller_profile: pda::seller_profile(seller),
+            auction,
+            seller_token_account: *seller_token_account,
+            escrow_token_account: pda::escrow(&auction),
+            bid_escrow_account: pda::bid_escrow(&auction),
+            item_mint: *item_mint,
+            payment_mint: *payment_mint,
+            config: pda::config(),
+            token_program: *token_program,
+            payment_token_program: *payment_token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        args,
+    )
+}
+
+/// Bids on `auction`. Once it has bids, the current highest bidder's payment
+/// account must be passed to receive their refund. `token_program` owns the
+/// payment mint.
+pub fn place_bid(
+    bidder: &Pubkey,
+    auction_address: &Pubkey,
+    auction: &Auction,
+    bidder_payment_account: &Pubkey,
+    previous_bidder_payment_account: Option<&Pubkey>,
+    token_program: &Pubkey,
+    args: args::PlaceBid,
+) -> Instruction {
+    build(
+        accounts::PlaceBid {
+            bidder: *bidder,
+            auction: *auction_address,
+            bid_escrow_account: pda::bid_escrow(auction_address),
+            bidder_payment_account: *bidder_payment_account,
+            previous_bidder_payment_account: previous_bidder_payment_account.copied(),
+            payment_mint: auction.payment_mint,
+            config: pda::config(),
+            token_program: *token_program,
+        },
+        args,
+    )
+}
+
+/// Pays out a finished auction to the seller and the winner, closing the
+/// auction and its escrows to the seller.
+pub fn settle_auction(
+    auction_address: &Pubkey,
+    auction: &Auction,
+    winner_token_account: &Pubkey,
+    seller_payment_account: &Pubkey,
+    token_program: &Pubkey,
+    payment_token_program: &Pubkey,
+    creator_accounts: &[Pubkey],
+) -> Instruction {
+    let ix = build(
+        accounts::SettleAuction {
+            seller: auction.seller,
+            auction: *auction_address,
+            escrow_token_account: pda::escrow(auction_address),
+            bid_escrow_account: pda::bid_escrow(auction_address),
+            winner_token_account: *winner_token_account,
+            seller_payment_account: *seller_payment_account,
+            config: pda::config(),
+            fee_vault: pda::fee_vault(&auction.payment_mint),
+            royalty_config: pda::royalty_config(&auction.item_mint),
+            item_mint: auction.item_mint,
+            payment_mint: auction.payment_mint,
+            token_program: *token_program,
+            payment_token_program: *payment_token_program,
+        },
+        args::SettleAuction {},
+    );
+    with_creators(ix, creator_accounts)
+}
+
+/// Returns the items of an auction without bids to the seller, closing the
+/// auction and its escrows. Anything sent to the bid escrow goes to
+/// `seller_payment_account`.
+pub fn cancel_auction(
+    seller: &Pubkey,
+    auction_address: &Pubkey,
+    auction: &Auction,
+    seller_token_account: &Pubkey,
+    seller_payment_account: &Pubkey,
+    token_program: &Pubkey,
+    payment_token_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::CancelAuction {
+            seller: *seller,
+            auction: *auction_address,
+            escrow_token_account: pda::escrow(auction_address),
+            bid_escrow_account: pda::bid_escrow(auction_address),
+            seller_token_account: *seller_token_account,
+            seller_payment_account: *seller_payment_account,
+            item_mint: auction.item_mint,
+            payment_mint: auction.payment_mint,
+            token_program: *token_program,
+            payment_token_program: *payment_token_program,
+        },
+        args::CancelAuction {},
+    )
+}
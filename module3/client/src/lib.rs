@@ -0,0 +1,23 @@
+//! Rust client for the marketplace program.
+//!
+//! - [`pda`] derives every program address from its seeds.
+//! - [`instructions`] builds each instruction with its accounts in program
+//!   order, deriving PDAs so callers only supply wallets, mints and token
+//!   accounts.
+//! - [`state`] decodes program accounts, including listings still in an
+//!   older layout.
+//! - [`errors`] maps custom error codes back to [`ErrorCode`].
+//!
+//! Account and argument types are re-exported from the program crate, so the
+//! client always matches the program it is built against.
+
+pub mod errors;
+pub mod instructions;
+pub mod pda;
+pub mod state;
+
+pub use marketplace::{
+    instruction as args, Auction, BatchPurchase, BuyerReceipt, BuyerRestriction, Category, Creator,
+    DutchAuction, ErrorCode, Listing, ListingMetadata, MarketplaceConfig, Offer, PaymentKind,
+    RoyaltyConfig, SaleReceipt, SellerProfile, ID, LISTING_VERSION,
+};
@@ -0,0 +1,71 @@
+//! Program-derived addresses, one function per seed layout.
+
+use anchor_lang::prelude::Pubkey;
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &marketplace::ID).0
+}
+
+/// The global marketplace config.
+pub fn config() -> Pubkey {
+    find(&[b"config"])
+}
+
+/// Protocol fee vault for `payment_mint`, owned by the config.
+pub fn fee_vault(payment_mint: &Pubkey) -> Pubkey {
+    find(&[b"fee_vault", payment_mint.as_ref()])
+}
+
+/// The seller's profile, which hands out listing nonces.
+pub fn seller_profile(seller: &Pubkey) -> Pubkey {
+    find(&[b"seller_profile", seller.as_ref()])
+}
+
+/// The seller's listing created with `nonce`.
+pub fn listing(seller: &Pubkey, nonce: u64) -> Pubkey {
+    find(&[b"listing", seller.as_ref(), nonce.to_le_bytes().as_ref()])
+}
+
+/// Item escrow of a listing or an auction.
+pub fn escrow(owner: &Pubkey) -> Pubkey {
+    find(&[b"escrow", owner.as_ref()])
+}
+
+/// Creator royalties for `item_mint`; may not exist.
+pub fn royalty_config(item_mint: &Pubkey) -> Pubkey {
+    find(&[b"royalty", item_mint.as_ref()])
+}
+
+/// Running total of what `buyer` has bought from a capped listing.
+pub fn buyer_receipt(listing: &Pubkey, buyer: &Pubkey) -> Pubkey {
+    find(&[b"buyer_receipt", listing.as_ref(), buyer.as_ref()])
+}
+
+/// Receipt of a listing's sale number `sale_count` (zero-based).
+pub fn sale_receipt(listing: &Pubkey, sale_count: u64) -> Pubkey {
+    find(&[
+        b"sale_receipt",
+        listing.as_ref(),
+        sale_count.to_le_bytes().as_ref(),
+    ])
+}
+
+/// The buyer's standing offer for `item_mint`.
+pub fn offer(buyer: &Pubkey, item_mint: &Pubkey) -> Pubkey {
+    find(&[b"offer", buyer.as_ref(), item_mint.as_ref()])
+}
+
+/// Payment escrow of an offer.
+pub fn offer_escrow(offer: &Pubkey) -> Pubkey {
+    find(&[b"offer_escrow", offer.as_ref()])
+}
+
+/// The seller's auction created with `nonce`.
+pub fn auction(seller: &Pubkey, nonce: u64) -> Pubkey {
+    find(&[b"auction", seller.as_ref(), nonce.to_le_bytes().as_ref()])
+}
+
+/// Escrow holding an auction's highest bid.
+pub fn bid_escrow(auction: &Pubkey) -> Pubkey {
+    find(&[b"bid_escrow", auction.as_ref()])
+}
@@ -0,0 +1,31 @@
+//! Decoding of marketplace accounts.
+
+use anchor_lang::{prelude::*, Discriminator};
+use marketplace::{Listing, ListingV0};
+
+/// Decodes any marketplace account, checking its discriminator.
+pub fn deserialize<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Decodes a listing in either the current or the original layout, so
+/// callers need not wait for `migrate_listing` before reading one. Original
+/// listings come back as version 0 with the newer fields defaulted; their
+/// payment mint is unknown until the seller migrates them.
+pub fn listing(data: &[u8]) -> Result<Listing> {
+    if data.len() >= Listing::LEN {
+        return deserialize(data);
+    }
+
+    require!(
+        data.len() >= 8 && data[..8] == Listing::DISCRIMINATOR,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    let legacy = ListingV0::deserialize(&mut &data[8..])?;
+    Ok(Listing::from(legacy))
+}
+
+/// Whether a listing account still needs `migrate_listing`.
+pub fn is_legacy_listing(data: &[u8]) -> bool {
+    data.len() < Listing::LEN
+}
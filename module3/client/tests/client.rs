@@ -0,0 +1,223 @@
+#[path = "../../programs/marketplace/tests/common/mod.rs"]
+mod common;
+
+use anchor_lang::{AccountSerialize, AnchorSerialize, Discriminator};
+use anchor_spl::token::spl_token;
+use common::*;
+use marketplace::ListingV0;
+use marketplace_client::{args, instructions, pda, state, Listing, LISTING_VERSION};
+use solana_sdk::signer::Signer;
+
+#[test]
+fn derives_the_program_seeds() {
+    let seller = solana_sdk::pubkey::Pubkey::new_unique();
+    let listing = pda::listing(&seller, 3);
+
+    assert_eq!(listing, listing_pda(&seller, 3));
+    assert_eq!(pda::escrow(&listing), escrow_pda(&listing));
+    assert_eq!(pda::config(), config_pda());
+    assert_eq!(pda::seller_profile(&seller), seller_profile_pda(&seller));
+}
+
+#[tokio::test]
+async fn builds_create_listing_in_program_order() {
+    let mut h = Harness::new().await;
+    let expected = build(
+        h.create_listing_accounts().await,
+        listing_args(ITEM_PRICE, ITEM_QUANTITY),
+    );
+
+    let ix = instructions::create_listing(
+        &h.seller.pubkey(),
+        0,
+        &h.seller_item_account,
+        &h.item_mint,
+        Some(&h.payment_mint),
+        &spl_token::ID,
+        listing_args(ITEM_PRICE, ITEM_QUANTITY),
+    );
+
+    assert_eq!(ix, expected);
+}
+
+#[tokio::test]
+async fn lists_buys_and_cancels() {
+    let mut h = Harness::new().await;
+    let seller = h.seller.pubkey();
+    let buyer = h.buyer.pubkey();
+
+    let create = instructions::create_listing(
+        &seller,
+        0,
+        &h.seller_item_account,
+        &h.item_mint,
+        Some(&h.payment_mint),
+        &spl_token::ID,
+        listing_args(ITEM_PRICE, ITEM_QUANTITY),
+    );
+    process(&mut h.context, &[create], &[&h.seller])
+        .await
+        .unwrap();
+
+    let address = pda::listing(&seller, 0);
+    let account = h
+        .context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    let listing = state::listing(&account.data).unwrap();
+    assert_eq!(listing.seller, seller);
+    assert_eq!(listing.quantity, ITEM_QUANTITY);
+
+    let payment = instructions::SplPayment {
+        buyer_payment_account: h.buyer_payment_account,
+        seller_payment_account: h.seller_payment_account,
+        token_program: spl_token::ID,
+    };
+    let purchase = instructions::purchase(
+        &buyer,
+        &address,
+        &listing,
+        &h.item_mint,
+        &h.buyer_item_account,
+        &spl_token::ID,
+        Some(&payment),
+        true,
+        &[],
+        purchase_args(2, ITEM_PRICE),
+    );
+    process(&mut h.context, &[purchase], &[&h.buyer])
+        .await
+        .unwrap();
+
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 2);
+    assert!(h.account_exists(pda::sale_receipt(&address, 0)).await);
+
+    let cancel = instructions::cancel_listing(
+        &seller,
+        &address,
+        &h.seller_item_account,
+        &h.item_mint,
+        &spl_token::ID,
+    );
+    process(&mut h.context, &[cancel], &[&h.seller])
+        .await
+        .unwrap();
+
+    assert!(!h.account_exists(address).await);
+    assert_eq!(
+        h.token_balance(h.seller_item_account).await,
+        STARTING_TOKENS - 2
+    );
+}
+
+#[tokio::test]
+async fn purchases_a_batch_through_the_client() {
+    let mut h = Harness::new().await;
+    let first = h.create_listing().await;
+    let second = h.create_listing().await;
+    let listing: Listing = h.account(first).await;
+    let payment = instructions::SplPayment {
+        buyer_payment_account: h.buyer_payment_account,
+        seller_payment_account: h.seller_payment_account,
+        token_program: spl_token::ID,
+    };
+    let item = |listing_address, quantity| instructions::BatchItem {
+        listing_address,
+        listing: &listing,
+        item_mint: h.item_mint,
+        buyer_token_account: h.buyer_item_account,
+        token_program: spl_token::ID,
+        payment: Some(payment),
+        creator_accounts: vec![],
+        quantity,
+        max_price: ITEM_PRICE,
+    };
+
+    let ix = instructions::purchase_batch(&h.buyer.pubkey(), &[item(first, 1), item(second, 2)]).unwrap();
+    process(&mut h.context, &[ix], &[&h.buyer]).await.unwrap();
+
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 3);
+    let second: Listing = h.account(second).await;
+    assert_eq!(second.quantity, ITEM_QUANTITY - 2);
+}
+
+#[tokio::test]
+async fn pauses_through_the_client() {
+    let mut h = Harness::new().await;
+
+    let ix = instructions::set_paused(&h.admin.pubkey(), args::SetPaused { paused: true });
+    process(&mut h.context, &[ix], &[&h.admin]).await.unwrap();
+
+    let config: marketplace_client::MarketplaceConfig = h.account(pda::config()).await;
+    assert!(config.paused);
+}
+
+#[test]
+fn decodes_legacy_listings() {
+    let listing = Listing {
+        version: LISTING_VERSION,
+        seller: solana_sdk::pubkey::Pubkey::new_unique(),
+        nonce: 7,
+        payment_kind: marketplace_client::PaymentKind::Native,
+        payment_mint: Default::default(),
+        price: ITEM_PRICE,
+        quantity: ITEM_QUANTITY,
+        name: ITEM_NAME.to_string(),
+        metadata: no_metadata(),
+        dutch_auction: None,
+        expires_at: None,
+        buyer_restriction: None,
+        max_per_buyer: None,
+        sale_count: 0,
+        units_sold: 0,
+        total_volume: 0,
+        active: true,
+        bump: 255,
+    };
+    let mut data = Vec::new();
+    listing.try_serialize(&mut data).unwrap();
+    data.resize(Listing::LEN, 0);
+    assert!(!state::is_legacy_listing(&data));
+
+    // The original layout: seller, price, quantity, name, active, bump
+    let mut data = Listing::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(listing.seller.as_ref());
+    data.extend_from_slice(&ITEM_PRICE.to_le_bytes());
+    data.extend_from_slice(&ITEM_QUANTITY.to_le_bytes());
+    ITEM_NAME.to_string().serialize(&mut data).unwrap();
+    data.push(1);
+    data.push(254);
+    data.resize(ListingV0::LEN, 0);
+    assert!(state::is_legacy_listing(&data));
+
+    let decoded = state::listing(&data).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert_eq!(decoded.seller, listing.seller);
+    assert_eq!(decoded.price, ITEM_PRICE);
+    assert_eq!(decoded.quantity, ITEM_QUANTITY);
+    assert_eq!(decoded.name, listing.name);
+    assert!(decoded.active);
+    assert_eq!(decoded.bump, 254);
+}
+
+#[test]
+fn rejects_other_accounts_as_listings() {
+    assert!(state::listing(&[0; 64]).is_err());
+}
+
+#[test]
+fn decodes_program_error_codes() {
+    use marketplace_client::{errors, ErrorCode};
+
+    let error = errors::from_code(u32::from(ErrorCode::MarketplacePaused)).unwrap();
+    assert_eq!(error.to_string(), "Marketplace is paused");
+    assert_eq!(
+        errors::from_code(u32::from(ErrorCode::InvalidPrice)).map(u32::from),
+        Some(u32::from(ErrorCode::InvalidPrice))
+    );
+    assert!(errors::from_code(0).is_none());
+    assert!(errors::from_code(u32::from(ErrorCode::ListingNeedsMigration) + 1).is_none());
+}
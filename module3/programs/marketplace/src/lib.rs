@@ -1,285 +1,3925 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::{keccak, program::invoke, program_option::COption};
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    transfer_fee::{instruction::harvest_withheld_tokens_to_mint, TransferFeeAmount, TransferFeeConfig},
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::{Account as TokenAccountState, Mint as MintState};
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+use num_derive::FromPrimitive;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Basis points in 100%.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Upper bound on the protocol fee (10%).
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Upper bound on the combined creator royalties (50%).
+pub const MAX_ROYALTY_BPS: u16 = 5_000;
+
+/// Maximum number of creators on a royalty config.
+pub const MAX_CREATORS: usize = 5;
+
+/// Accounts per listing in `purchase_batch`'s remaining accounts, before
+/// the listing's creator accounts.
+pub const BATCH_PURCHASE_ACCOUNTS: usize = 13;
+
+/// Current `Listing` account layout; older accounts are upgraded with
+/// `migrate_listing`.
+pub const LISTING_VERSION: u8 = 1;
+
+/// Maximum length of a listing or auction name in UTF-8 bytes.
+pub const MAX_NAME_BYTES: usize = 32;
+
+/// Maximum length of a listing description in UTF-8 bytes.
+pub const MAX_DESCRIPTION_BYTES: usize = 256;
+
+/// Maximum length of a listing's off-chain metadata URI in UTF-8 bytes.
+pub const MAX_URI_BYTES: usize = 200;
+
+/// Maximum number of tags on a listing.
+pub const MAX_TAGS: usize = 5;
+
+/// Maximum length of a single listing tag in UTF-8 bytes.
+pub const MAX_TAG_BYTES: usize = 16;
+
 #[program]
 pub mod marketplace {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_listing(
         ctx: Context<CreateListing>,
         price: u64,
         quantity: u64,
         name: String,
+        dutch_auction: Option<DutchAuction>,
+        expires_at: Option<i64>,
+        buyer_restriction: Option<BuyerRestriction>,
+        max_per_buyer: Option<u64>,
+        metadata: ListingMetadata,
     ) -> Result<()> {
-        // Validate inputs
-        require!(price > 0, ErrorCode::InvalidPrice);
-        require!(quantity > 0, ErrorCode::InvalidQuantity);
-        require!(name.len() > 0, ErrorCode::InvalidName);
-        require!(name.len() <= 32, ErrorCode::NameTooLong);
+        open_listing(
+            ctx,
+            price,
+            quantity,
+            name,
+            dutch_auction,
+            expires_at,
+            buyer_restriction,
+            max_per_buyer,
+            metadata,
+            false,
+        )
+    }
 
+    /// Lists items priced in units of another item token, `payment_mint`,
+    /// which buyers trade for them with `swap`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_swap_listing(
+        ctx: Context<CreateListing>,
+        price: u64,
+        quantity: u64,
+        name: String,
+        dutch_auction: Option<DutchAuction>,
+        expires_at: Option<i64>,
+        buyer_restriction: Option<BuyerRestriction>,
+        max_per_buyer: Option<u64>,
+        metadata: ListingMetadata,
+    ) -> Result<()> {
+        open_listing(
+            ctx,
+            price,
+            quantity,
+            name,
+            dutch_auction,
+            expires_at,
+            buyer_restriction,
+            max_per_buyer,
+            metadata,
+            true,
+        )
+    }
+
+    /// Buys `quantity` items from a listing. The buyer bounds what they pay
+    /// twice: `max_price` caps the unit price, which a Dutch auction may
+    /// have moved since the buyer signed, and is the same bound `swap` and
+    /// `purchase_batch` take; `max_total` caps the whole charge. Both must
+    /// hold, so whichever is tighter wins, and either fails the purchase
+    /// with `PriceMismatch`. Pass `max_price * quantity` as `max_total` to
+    /// only bound the unit price.
+    pub fn purchase<'info>(
+        ctx: Context<'_, '_, '_, 'info, Purchase<'info>>,
+        quantity: u64,
+        max_price: u64,
+        max_total: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        // Validate inputs
+        let listing_info = ctx.accounts.listing.to_account_info();
         let listing = &mut ctx.accounts.listing;
-        let seller = &ctx.accounts.seller;
+        let now = Clock::get()?.unix_timestamp;
+        let unit_price = listing.quote(&ctx.accounts.buyer.key(), quantity, max_price, &proof, now)?;
 
-        // Initialize listing account
-        listing.seller = seller.key();
-        listing.price = price;
-        listing.quantity = quantity;
-        listing.name = name;
-        listing.active = true;
-        listing.bump = *ctx.bumps.get("listing").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+        let total_price = unit_price.checked_mul(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        require!(total_price <= max_total, ErrorCode::PriceMismatch);
 
-        // Initialize escrow account
-        token::transfer(
-            CpiContext::new(
+        // Track what this buyer has bought from the listing against its cap
+        track_buyer_receipt(
+            &mut ctx.accounts.buyer_receipt,
+            ctx.bumps.get("buyer_receipt"),
+            listing,
+            ctx.accounts.buyer.key(),
+            quantity,
+        )?;
+
+        let fee = ctx.accounts.config.fee_for(total_price)?;
+
+        // Buyer pays in SPL tokens or lamports depending on the listing
+        let (source, fee_destination, seller_destination) = match listing.payment_kind {
+            PaymentKind::Spl => (
+                PaymentSource::Spl {
+                    from: required(&ctx.accounts.buyer_payment_account)?,
+                    mint: required(&ctx.accounts.payment_mint)?,
+                    decimals: ctx.accounts.payment_mint.as_ref().map_or(0, |mint| mint.decimals),
+                    authority: ctx.accounts.buyer.to_account_info(),
+                    token_program: required(&ctx.accounts.payment_token_program)?,
+                    signer: &[],
+                },
+                ctx.accounts.fee_vault.as_ref().map(|fee_vault| fee_vault.to_account_info()),
+                required(&ctx.accounts.seller_payment_account)?,
+            ),
+            PaymentKind::Native => (
+                PaymentSource::Native {
+                    from: ctx.accounts.buyer.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                Some(ctx.accounts.config.to_account_info()),
+                ctx.accounts.seller.to_account_info(),
+            ),
+            PaymentKind::Swap => return err!(ErrorCode::SwapListing),
+        };
+
+        // Pay creator royalties from buyer before the seller
+        let royalties = pay_royalties(
+            &ctx.accounts.royalty_config,
+            ctx.remaining_accounts,
+            listing.payment_mint,
+            total_price,
+            &source,
+        )?;
+
+        let seller_proceeds = total_price.checked_sub(fee)
+            .and_then(|amount| amount.checked_sub(royalties))
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        // Transfer protocol fee from buyer to fee vault
+        pay_fee(&source, fee_destination.as_ref(), fee)?;
+
+        // Transfer payment from buyer to seller
+        source.pay(&seller_destination, seller_proceeds)?;
+
+        // Transfer items from escrow to buyer
+        let nonce = listing.nonce.to_le_bytes();
+        let seeds = &[
+            b"listing",
+            listing.seller.as_ref(),
+            nonce.as_ref(),
+            &[listing.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.seller_token_account.to_account_info(),
-                    to: ctx.accounts.escrow_token_account.to_account_info(),
-                    authority: seller.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.buyer_token_account.to_account_info(),
+                    authority: listing_info,
                 },
+                signer,
             ),
             quantity,
+            ctx.accounts.item_mint.decimals,
         )?;
 
-        msg!("Listing created successfully!");
+        record_sale_receipt(
+            &mut ctx.accounts.sale_receipt,
+            ctx.bumps.get("sale_receipt"),
+            listing.key(),
+            ctx.accounts.buyer.key(),
+            quantity,
+            unit_price,
+        )?;
+
+        // Update listing
+        listing.record_sale(quantity, total_price)?;
+
+        emit!(ListingPurchased {
+            listing: listing.key(),
+            buyer: ctx.accounts.buyer.key(),
+            seller: listing.seller,
+            quantity,
+            unit_price,
+            total_price,
+            fee,
+            royalties,
+            remaining_quantity: listing.quantity,
+        });
+
+        msg!("Purchase completed successfully!");
         Ok(())
     }
 
-    pub fn purchase(ctx: Context<Purchase>, quantity: u64) -> Result<()> {
-        // Validate inputs
+    /// Buys from several listings in one all-or-nothing instruction. Each
+    /// item's `listing_index` points at the first of its listing's
+    /// `BATCH_PURCHASE_ACCOUNTS` remaining accounts, in this order: listing,
+    /// seller, escrow token account, buyer token account, item mint, royalty
+    /// config, buyer payment account, seller payment account, payment mint,
+    /// fee vault, buyer receipt, token program and payment token program,
+    /// followed by the royalty config's creator accounts. The SPL payment
+    /// accounts are the program id for lamport-priced listings, and the buyer
+    /// receipt is the program id for listings without a per-buyer cap.
+    ///
+    /// Listings with an allowlist must be bought with `purchase`.
+    pub fn purchase_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, PurchaseBatch<'info>>,
+        items: Vec<BatchPurchase>,
+    ) -> Result<()> {
+        require!(!items.is_empty(), ErrorCode::InvalidQuantity);
+
+        let buyer = ctx.accounts.buyer.to_account_info();
+        let now = Clock::get()?.unix_timestamp;
+        let mut purchased: Vec<Pubkey> = Vec::with_capacity(items.len());
+
+        for item in &items {
+            let start = item.listing_index as usize;
+            let accounts: &[AccountInfo<'info>; BATCH_PURCHASE_ACCOUNTS] = ctx.remaining_accounts
+                .get(start..start + BATCH_PURCHASE_ACCOUNTS)
+                .and_then(|accounts| accounts.try_into().ok())
+                .ok_or(ErrorCode::InvalidBatchIndex)?;
+            let [
+                listing_info,
+                seller,
+                escrow_info,
+                buyer_token_account,
+                item_mint_info,
+                royalty_config,
+                buyer_payment_account,
+                seller_payment_account,
+                payment_mint_info,
+                fee_vault,
+                buyer_receipt_info,
+                token_program,
+                payment_token_program,
+            ] = accounts;
+            let creator_accounts = &ctx.remaining_accounts[start + BATCH_PURCHASE_ACCOUNTS..];
+
+            // A listing bought twice would have its first sale overwritten
+            require!(!purchased.contains(listing_info.key), ErrorCode::DuplicateListing);
+            purchased.push(listing_info.key());
+
+            // Validate the listing and escrow as `Purchase` does
+            let mut listing = Account::<Listing>::try_from(listing_info)?;
+            require!(listing_info.is_writable, anchor_lang::error::ErrorCode::ConstraintMut);
+            let listing_address = Pubkey::create_program_address(
+                &[
+                    b"listing",
+                    listing.seller.as_ref(),
+                    listing.nonce.to_le_bytes().as_ref(),
+                    &[listing.bump],
+                ],
+                &crate::ID,
+            ).map_err(|_| anchor_lang::error::ErrorCode::ConstraintSeeds)?;
+            require_keys_eq!(listing_address, listing.key(), anchor_lang::error::ErrorCode::ConstraintSeeds);
+            require_keys_eq!(seller.key(), listing.seller, ErrorCode::InvalidSeller);
+
+            let escrow = InterfaceAccount::<TokenAccount>::try_from(escrow_info)?;
+            require_keys_eq!(escrow.owner, listing.key(), ErrorCode::InvalidEscrowOwner);
+            let buyer_token = InterfaceAccount::<TokenAccount>::try_from(buyer_token_account)?;
+            require_keys_eq!(buyer_token.owner, buyer.key(), ErrorCode::InvalidOwner);
+            let item_mint = InterfaceAccount::<Mint>::try_from(item_mint_info)?;
+            require_keys_eq!(item_mint.key(), escrow.mint, ErrorCode::InvalidMint);
+            require_keys_eq!(token_program.key(), *item_mint_info.owner, ErrorCode::TokenProgramMismatch);
+            let (royalty_address, _) = Pubkey::find_program_address(
+                &[b"royalty", escrow.mint.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(royalty_config.key(), royalty_address, anchor_lang::error::ErrorCode::ConstraintSeeds);
+
+            let unit_price = listing.quote(&buyer.key(), item.quantity, item.max_price, &[], now)?;
+
+            // Enforce per-buyer caps as `Purchase` does, creating the receipt
+            // on the buyer's first purchase
+            let mut buyer_receipt = None;
+            let mut buyer_receipt_bump = None;
+            if buyer_receipt_info.key() != crate::ID {
+                let (receipt_address, bump) = Pubkey::find_program_address(
+                    &[b"buyer_receipt", listing.key().as_ref(), buyer.key.as_ref()],
+                    &crate::ID,
+                );
+                require_keys_eq!(buyer_receipt_info.key(), receipt_address, anchor_lang::error::ErrorCode::ConstraintSeeds);
+                require!(buyer_receipt_info.is_writable, anchor_lang::error::ErrorCode::ConstraintMut);
+                if buyer_receipt_info.data_is_empty() {
+                    let listing_key = listing.key();
+                    create_pda_account(
+                        &buyer,
+                        buyer_receipt_info,
+                        &ctx.accounts.system_program.to_account_info(),
+                        BuyerReceipt::LEN,
+                        &[&[b"buyer_receipt", listing_key.as_ref(), buyer.key.as_ref(), &[bump]]],
+                    )?;
+                    buyer_receipt = Some(Account::<BuyerReceipt>::try_from_unchecked(buyer_receipt_info)?);
+                } else {
+                    buyer_receipt = Some(Account::<BuyerReceipt>::try_from(buyer_receipt_info)?);
+                }
+                buyer_receipt_bump = Some(bump);
+            }
+            track_buyer_receipt(
+                &mut buyer_receipt,
+                buyer_receipt_bump.as_ref(),
+                &listing,
+                buyer.key(),
+                item.quantity,
+            )?;
+
+            let total_price = unit_price.checked_mul(item.quantity)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+            let fee = ctx.accounts.config.fee_for(total_price)?;
+
+            let (source, fee_destination, seller_destination) = match listing.payment_kind {
+                PaymentKind::Spl => {
+                    let buyer_payment = InterfaceAccount::<TokenAccount>::try_from(batch_payment_account(buyer_payment_account)?)?;
+                    require_keys_eq!(buyer_payment.mint, listing.payment_mint, ErrorCode::InvalidBuyerPaymentMint);
+                    let seller_payment = InterfaceAccount::<TokenAccount>::try_from(batch_payment_account(seller_payment_account)?)?;
+                    require_keys_eq!(seller_payment.mint, listing.payment_mint, ErrorCode::InvalidSellerPaymentMint);
+                    require_keys_eq!(seller_payment.owner, listing.seller, ErrorCode::InvalidSellerPaymentOwner);
+                    let payment_mint = InterfaceAccount::<Mint>::try_from(batch_payment_account(payment_mint_info)?)?;
+                    require_keys_eq!(payment_mint.key(), listing.payment_mint, ErrorCode::InvalidMint);
+                    require_keys_eq!(
+                        batch_payment_account(payment_token_program)?.key(),
+                        *payment_mint_info.owner,
+                        ErrorCode::TokenProgramMismatch
+                    );
+                    let (fee_vault_address, _) = Pubkey::find_program_address(
+                        &[b"fee_vault", listing.payment_mint.as_ref()],
+                        &crate::ID,
+                    );
+                    require_keys_eq!(
+                        batch_payment_account(fee_vault)?.key(),
+                        fee_vault_address,
+                        anchor_lang::error::ErrorCode::ConstraintSeeds
+                    );
+
+                    (
+                        PaymentSource::Spl {
+                            from: buyer_payment_account.clone(),
+                            mint: payment_mint_info.clone(),
+                            decimals: payment_mint.decimals,
+                            authority: buyer.clone(),
+                            token_program: payment_token_program.clone(),
+                            signer: &[],
+                        },
+                        fee_vault.clone(),
+                        seller_payment_account.clone(),
+                    )
+                }
+                PaymentKind::Native => (
+                    PaymentSource::Native {
+                        from: buyer.clone(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                    },
+                    ctx.accounts.config.to_account_info(),
+                    seller.clone(),
+                ),
+                PaymentKind::Swap => return err!(ErrorCode::SwapListing),
+            };
+
+            // Pay creator royalties from buyer before the seller
+            let royalties = pay_royalties(
+                royalty_config,
+                creator_accounts,
+                listing.payment_mint,
+                total_price,
+                &source,
+            )?;
+
+            let seller_proceeds = total_price.checked_sub(fee)
+                .and_then(|amount| amount.checked_sub(royalties))
+                .ok_or(ErrorCode::NumericalOverflow)?;
+
+            pay_fee(&source, Some(&fee_destination), fee)?;
+            source.pay(&seller_destination, seller_proceeds)?;
+
+            // Transfer items from escrow to buyer
+            let nonce = listing.nonce.to_le_bytes();
+            let seeds = &[
+                b"listing",
+                listing.seller.as_ref(),
+                nonce.as_ref(),
+                &[listing.bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.clone(),
+                    TransferChecked {
+                        from: escrow_info.clone(),
+                        mint: item_mint_info.clone(),
+                        to: buyer_token_account.clone(),
+                        authority: listing_info.clone(),
+                    },
+                    signer,
+                ),
+                item.quantity,
+                item_mint.decimals,
+            )?;
+
+            // Listings and receipts are loaded by hand here, so write them
+            // back explicitly
+            listing.record_sale(item.quantity, total_price)?;
+            listing.exit(&crate::ID)?;
+            if let Some(buyer_receipt) = &buyer_receipt {
+                buyer_receipt.exit(&crate::ID)?;
+            }
+
+            emit!(ListingPurchased {
+                listing: listing.key(),
+                buyer: buyer.key(),
+                seller: listing.seller,
+                quantity: item.quantity,
+                unit_price,
+                total_price,
+                fee,
+                royalties,
+                remaining_quantity: listing.quantity,
+            });
+        }
+
+        msg!("Batch purchase completed successfully!");
+        Ok(())
+    }
+
+    /// Trades the buyer's `payment_mint` tokens for items from a swap
+    /// listing. The tokens go straight to the seller, without protocol fees or
+    /// royalties, grossed up for any Token-2022 transfer fee so the seller
+    /// receives the full price.
+    pub fn swap(
+        ctx: Context<Swap>,
+        quantity: u64,
+        max_price: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let listing_info = ctx.accounts.listing.to_account_info();
         let listing = &mut ctx.accounts.listing;
-        require!(listing.active, ErrorCode::ListingNotActive);
-        require!(quantity > 0, ErrorCode::InvalidQuantity);
-        require!(quantity <= listing.quantity, ErrorCode::InsufficientQuantity);
+        let now = Clock::get()?.unix_timestamp;
+        let unit_price = listing.quote(&ctx.accounts.buyer.key(), quantity, max_price, &proof, now)?;
 
-        let total_price = listing.price.checked_mul(quantity)
+        let total_price = unit_price.checked_mul(quantity)
             .ok_or(ErrorCode::NumericalOverflow)?;
 
-        // Transfer payment from buyer to seller
-        token::transfer(
+        track_buyer_receipt(
+            &mut ctx.accounts.buyer_receipt,
+            ctx.bumps.get("buyer_receipt"),
+            listing,
+            ctx.accounts.buyer.key(),
+            quantity,
+        )?;
+
+        // Transfer the buyer's tokens to the seller
+        token_interface::transfer_checked(
             CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                ctx.accounts.payment_token_program.to_account_info(),
+                TransferChecked {
                     from: ctx.accounts.buyer_payment_account.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
                     to: ctx.accounts.seller_payment_account.to_account_info(),
                     authority: ctx.accounts.buyer.to_account_info(),
                 },
             ),
-            total_price,
+            with_transfer_fee(&ctx.accounts.payment_mint, total_price)?,
+            ctx.accounts.payment_mint.decimals,
         )?;
 
         // Transfer items from escrow to buyer
+        let nonce = listing.nonce.to_le_bytes();
         let seeds = &[
             b"listing",
             listing.seller.as_ref(),
-            listing.name.as_bytes(),
+            nonce.as_ref(),
             &[listing.bump],
         ];
         let signer = &[&seeds[..]];
 
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                TransferChecked {
                     from: ctx.accounts.escrow_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
                     to: ctx.accounts.buyer_token_account.to_account_info(),
-                    authority: ctx.accounts.listing.to_account_info(),
+                    authority: listing_info,
                 },
                 signer,
             ),
             quantity,
+            ctx.accounts.item_mint.decimals,
         )?;
 
-        // Update listing
-        listing.quantity = listing.quantity.checked_sub(quantity)
-            .ok_or(ErrorCode::NumericalOverflow)?;
-        
-        if listing.quantity == 0 {
-            listing.active = false;
-        }
+        record_sale_receipt(
+            &mut ctx.accounts.sale_receipt,
+            ctx.bumps.get("sale_receipt"),
+            listing.key(),
+            ctx.accounts.buyer.key(),
+            quantity,
+            unit_price,
+        )?;
 
-        msg!("Purchase completed successfully!");
+        listing.record_sale(quantity, total_price)?;
+
+        emit!(ListingPurchased {
+            listing: listing.key(),
+            buyer: ctx.accounts.buyer.key(),
+            seller: listing.seller,
+            quantity,
+            unit_price,
+            total_price,
+            fee: 0,
+            royalties: 0,
+            remaining_quantity: listing.quantity,
+        });
+
+        msg!("Swap completed successfully!");
         Ok(())
     }
 
     pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
-        let listing = &mut ctx.accounts.listing;
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &ctx.accounts.listing;
         require!(listing.active, ErrorCode::ListingNotActive);
         
-        // Transfer items from escrow back to seller
+        // Transfer everything in escrow back to seller, including any tokens
+        // sent to it directly, so it can be closed
+        let returned = ctx.accounts.escrow_token_account.amount;
+        let nonce = listing.nonce.to_le_bytes();
         let seeds = &[
             b"listing",
             listing.seller.as_ref(),
-            listing.name.as_bytes(),
+            nonce.as_ref(),
             &[listing.bump],
         ];
         let signer = &[&seeds[..]];
 
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                TransferChecked {
                     from: ctx.accounts.escrow_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
                     to: ctx.accounts.seller_token_account.to_account_info(),
-                    authority: ctx.accounts.listing.to_account_info(),
+                    authority: listing_info.clone(),
                 },
                 signer,
             ),
-            listing.quantity,
+            returned,
+            ctx.accounts.item_mint.decimals,
+        )?;
+
+        // Close the empty escrow; the listing itself is closed to the seller on exit
+        harvest_withheld_fees(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.escrow_token_account.to_account_info(),
         )?;
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_token_account.to_account_info(),
+                destination: ctx.accounts.seller.to_account_info(),
+                authority: listing_info,
+            },
+            signer,
+        ))?;
 
-        // Mark listing as inactive
-        listing.active = false;
-        listing.quantity = 0;
+        emit!(ListingCancelled {
+            listing: listing.key(),
+            seller: listing.seller,
+            quantity_returned: returned,
+        });
 
         msg!("Listing cancelled successfully!");
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(price: u64, quantity: u64, name: String)]
-pub struct CreateListing<'info> {
-    #[account(mut)]
-    pub seller: Signer<'info>,
-    
-    #[account(
-        init,
-        payer = seller,
-        space = Listing::LEN,
-        seeds = [b"listing", seller.key().as_ref(), name.as_bytes()],
-        bump
-    )]
-    pub listing: Account<'info, Listing>,
-    
-    #[account(
-        mut,
-        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
-    )]
-    pub seller_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        init,
-        payer = seller,
-        seeds = [b"escrow", listing.key().as_ref()],
-        bump,
-        token::mint = item_mint,
-        token::authority = listing,
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    pub item_mint: Account<'info, token::Mint>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    pub fn expire_listing(ctx: Context<ExpireListing>) -> Result<()> {
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &ctx.accounts.listing;
+        require!(
+            listing.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ListingNotExpired
+        );
 
-#[derive(Accounts)]
-#[instruction(quantity: u64)]
-pub struct Purchase<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"listing", listing.seller.as_ref(), listing.name.as_bytes()],
-        bump = listing.bump,
-    )]
-    pub listing: Account<'info, Listing>,
+        // Return any unsold items, and anything else sent to the escrow, to
+        // the seller
+        let returned = ctx.accounts.escrow_token_account.amount;
+        let nonce = listing.nonce.to_le_bytes();
+        let seeds = &[
+            b"listing",
+            listing.seller.as_ref(),
+            nonce.as_ref(),
+            &[listing.bump],
+        ];
+        let signer = &[&seeds[..]];
 
-    /// CHECK: This is the seller's address from the listing
-    #[account(
-        mut,
-        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
-    )]
-    pub seller: AccountInfo<'info>,
-    
-    #[account(
-        mut,
-        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        mut,
-        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner
-    )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub buyer_payment_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub seller_payment_account: Account<'info, TokenAccount>,
-    
-    pub token_program: Program<'info, Token>,
-}
+        if returned > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow_token_account.to_account_info(),
+                        mint: ctx.accounts.item_mint.to_account_info(),
+                        to: ctx.accounts.seller_token_account.to_account_info(),
+                        authority: listing_info.clone(),
+                    },
+                    signer,
+                ),
+                returned,
+                ctx.accounts.item_mint.decimals,
+            )?;
+        }
 
-#[derive(Accounts)]
-pub struct CancelListing<'info> {
-    #[account(
-        mut,
-        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
-    )]
-    pub seller: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"listing", listing.seller.as_ref(), listing.name.as_bytes()],
-        bump = listing.bump,
-    )]
-    pub listing: Account<'info, Listing>,
-    
-    #[account(
-        mut,
-        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        mut,
-        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
-    )]
-    pub seller_token_account: Account<'info, TokenAccount>,
-    
-    pub token_program: Program<'info, Token>,
-}
+        // The escrow's rent tips the cranker; the listing itself is closed to
+        // the seller on exit
+        harvest_withheld_fees(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.escrow_token_account.to_account_info(),
+        )?;
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_token_account.to_account_info(),
+                destination: ctx.accounts.cranker.to_account_info(),
+                authority: listing_info,
+            },
+            signer,
+        ))?;
 
-#[account]
-pub struct Listing {
-    pub seller: Pubkey,
-    pub price: u64,
-    pub quantity: u64,
-    pub name: String,
-    pub active: bool,
-    pub bump: u8,
-}
+        emit!(ListingExpired {
+            listing: listing.key(),
+            seller: listing.seller,
+            cranker: ctx.accounts.cranker.key(),
+            quantity_returned: returned,
+        });
 
-impl Listing {
-    const LEN: usize = 8 + // discriminator
-        32 + // seller pubkey
-        8 +  // price
-        8 +  // quantity
-        4 + 32 + // name (String with max length of 32)
-        1 +  // active
-        1;   // bump
-}
+        msg!("Listing expired successfully!");
+        Ok(())
+    }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Invalid price, must be greater than zero")]
-    InvalidPrice,
-    
-    #[msg("Invalid quantity, must be greater than zero")]
-    InvalidQuantity,
+    pub fn close_listing(ctx: Context<CloseListing>) -> Result<()> {
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &ctx.accounts.listing;
+        require!(!listing.active, ErrorCode::ListingStillActive);
 
-    #[msg("Invalid name, must not be empty")]
-    InvalidName,
-    
-    #[msg("Name is too long, must be 32 characters or less")]
-    NameTooLong,
+        let nonce = listing.nonce.to_le_bytes();
+        let seeds = &[
+            b"listing",
+            listing.seller.as_ref(),
+            nonce.as_ref(),
+            &[listing.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Sweep any tokens sent to the escrow after it emptied to the seller
+        let leftover = ctx.accounts.escrow_token_account.amount;
+        if leftover > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow_token_account.to_account_info(),
+                        mint: ctx.accounts.item_mint.to_account_info(),
+                        to: ctx.accounts.seller_token_account.to_account_info(),
+                        authority: listing_info.clone(),
+                    },
+                    signer,
+                ),
+                leftover,
+                ctx.accounts.item_mint.decimals,
+            )?;
+        }
+
+        // Close the empty escrow; the listing itself is closed to the seller on exit
+        harvest_withheld_fees(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.escrow_token_account.to_account_info(),
+        )?;
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_token_account.to_account_info(),
+                destination: ctx.accounts.seller.to_account_info(),
+                authority: listing_info,
+            },
+            signer,
+        ))?;
+
+        emit!(ListingClosed {
+            listing: listing.key(),
+            seller: listing.seller,
+        });
+
+        msg!("Listing closed successfully!");
+        Ok(())
+    }
+
+    pub fn update_listing(
+        ctx: Context<UpdateListing>,
+        price: Option<u64>,
+        deposit: u64,
+        withdraw: u64,
+        metadata: Option<ListingMetadata>,
+    ) -> Result<()> {
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.active, ErrorCode::ListingNotActive);
+
+        // Replace the storefront metadata
+        if let Some(metadata) = metadata {
+            metadata.validate()?;
+            listing.metadata = metadata;
+        }
+
+        // Change the unit price
+        if let Some(price) = price {
+            require!(price > 0, ErrorCode::InvalidPrice);
+            if let Some(dutch_auction) = &listing.dutch_auction {
+                require!(dutch_auction.floor_price < price, ErrorCode::InvalidDutchAuction);
+            }
+            listing.price = price;
+        }
+
+        // Top up escrow with more items
+        if deposit > 0 {
+            let escrow_before = ctx.accounts.escrow_token_account.amount;
+
+            token_interface::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.seller_token_account.to_account_info(),
+                        mint: ctx.accounts.item_mint.to_account_info(),
+                        to: ctx.accounts.escrow_token_account.to_account_info(),
+                        authority: ctx.accounts.seller.to_account_info(),
+                    },
+                ),
+                deposit,
+                ctx.accounts.item_mint.decimals,
+            )?;
+
+            // Credit what actually arrived, net of any Token-2022 transfer fee
+            ctx.accounts.escrow_token_account.reload()?;
+            let received = ctx.accounts.escrow_token_account.amount.checked_sub(escrow_before)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+            listing.quantity = listing.quantity.checked_add(received)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+
+        // Withdraw items from escrow back to seller
+        if withdraw > 0 {
+            require!(withdraw <= listing.quantity, ErrorCode::InsufficientQuantity);
+
+            let nonce = listing.nonce.to_le_bytes();
+            let seeds = &[
+                b"listing",
+                listing.seller.as_ref(),
+                nonce.as_ref(),
+                &[listing.bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow_token_account.to_account_info(),
+                        mint: ctx.accounts.item_mint.to_account_info(),
+                        to: ctx.accounts.seller_token_account.to_account_info(),
+                        authority: listing_info,
+                    },
+                    signer,
+                ),
+                withdraw,
+                ctx.accounts.item_mint.decimals,
+            )?;
+
+            listing.quantity = listing.quantity.checked_sub(withdraw)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+
+        if listing.quantity == 0 {
+            listing.active = false;
+        }
+
+        emit!(ListingUpdated {
+            listing: listing.key(),
+            price: listing.price,
+            quantity: listing.quantity,
+            active: listing.active,
+        });
+
+        msg!("Listing updated successfully!");
+        Ok(())
+    }
+
+    /// Moves a listing created in the original `[b"listing", seller, name]`
+    /// layout to the seller's next nonce-derived listing address in the
+    /// current layout, carrying its escrowed items over. The original
+    /// layout recorded no payment mint, so the seller picks one, or none to
+    /// price the listing in lamports.
+    pub fn migrate_listing(ctx: Context<MigrateListing>) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_listing.to_account_info();
+
+        // Dispatch on the account size, the only thing that tells layouts apart
+        let legacy = {
+            let data = legacy_info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == Listing::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            match data.len() {
+                ListingV0::LEN => ListingV0::deserialize(&mut &data[8..])?,
+                Listing::LEN => return err!(ErrorCode::ListingUpToDate),
+                _ => return err!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize),
+            }
+        };
+        require_keys_eq!(legacy.seller, ctx.accounts.seller.key(), ErrorCode::InvalidSeller);
+        require!(legacy.active, ErrorCode::ListingNotActive);
+
+        let (legacy_seller, legacy_name, legacy_bump) = (legacy.seller, legacy.name.clone(), legacy.bump);
+        let legacy_seeds = &[
+            b"listing",
+            legacy_seller.as_ref(),
+            legacy_name.as_bytes(),
+            &[legacy_bump],
+        ];
+        let legacy_address = Pubkey::create_program_address(legacy_seeds, &crate::ID)
+            .map_err(|_| anchor_lang::error::ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(legacy_address, legacy_info.key(), anchor_lang::error::ErrorCode::ConstraintSeeds);
+        let legacy_signer = &[&legacy_seeds[..]];
+
+        // Claim the seller's next listing nonce
+        let seller = &ctx.accounts.seller;
+        let seller_profile = &mut ctx.accounts.seller_profile;
+        init_seller_profile(seller_profile, seller.key(), ctx.bumps.get("seller_profile"))?;
+        let nonce = seller_profile.listing_nonce;
+        seller_profile.listing_nonce = seller_profile.listing_nonce.checked_add(1)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.set_inner(Listing::from(legacy));
+        listing.version = LISTING_VERSION;
+        listing.nonce = nonce;
+        listing.bump = *ctx.bumps.get("listing").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+        match &ctx.accounts.payment_mint {
+            Some(payment_mint) => {
+                listing.payment_kind = PaymentKind::Spl;
+                listing.payment_mint = payment_mint.key();
+            }
+            None => {
+                listing.payment_kind = PaymentKind::Native;
+                listing.payment_mint = Pubkey::default();
+            }
+        }
+
+        // Move everything in the old escrow over, then close the old
+        // escrow and listing to the seller
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.legacy_escrow_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.escrow_token_account.to_account_info(),
+                    authority: legacy_info.clone(),
+                },
+                legacy_signer,
+            ),
+            ctx.accounts.legacy_escrow_account.amount,
+            ctx.accounts.item_mint.decimals,
+        )?;
+        close_escrow(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.legacy_escrow_account.to_account_info(),
+            &seller.to_account_info(),
+            &legacy_info,
+            legacy_signer,
+        )?;
+        close_legacy_account(&legacy_info, &seller.to_account_info())?;
+
+        emit!(ListingMigrated {
+            legacy_listing: legacy_info.key(),
+            listing: listing.key(),
+            version: listing.version,
+        });
+
+        msg!("Listing migrated successfully!");
+        Ok(())
+    }
+
+    pub fn create_offer(
+        ctx: Context<CreateOffer>,
+        price: u64,
+        quantity: u64,
+    ) -> Result<()> {
+        // Validate inputs
+        require!(price > 0, ErrorCode::InvalidPrice);
+        require!(quantity > 0, ErrorCode::InvalidQuantity);
+
+        let total_price = price.checked_mul(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        let offer = &mut ctx.accounts.offer;
+        let buyer = &ctx.accounts.buyer;
+
+        // Initialize offer account
+        offer.buyer = buyer.key();
+        offer.item_mint = ctx.accounts.item_mint.key();
+        offer.payment_mint = ctx.accounts.payment_mint.key();
+        offer.price = price;
+        offer.quantity = quantity;
+        offer.active = true;
+        offer.bump = *ctx.bumps.get("offer").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+        // Escrow the full payment for the offer, grossed up so the escrow
+        // still holds `total_price` after any Token-2022 transfer fee
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.buyer_payment_account.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
+                    to: ctx.accounts.escrow_payment_account.to_account_info(),
+                    authority: buyer.to_account_info(),
+                },
+            ),
+            with_transfer_fee(&ctx.accounts.payment_mint, total_price)?,
+            ctx.accounts.payment_mint.decimals,
+        )?;
+
+        emit!(OfferCreated {
+            offer: offer.key(),
+            buyer: offer.buyer,
+            item_mint: offer.item_mint,
+            payment_mint: offer.payment_mint,
+            price,
+            quantity,
+        });
+
+        msg!("Offer created successfully!");
+        Ok(())
+    }
+
+    pub fn accept_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>,
+        quantity: u64,
+    ) -> Result<()> {
+        // Validate inputs
+        let offer_info = ctx.accounts.offer.to_account_info();
+        let offer = &mut ctx.accounts.offer;
+        require!(offer.active, ErrorCode::OfferNotActive);
+        require!(quantity > 0, ErrorCode::InvalidQuantity);
+        require!(quantity <= offer.quantity, ErrorCode::InsufficientQuantity);
+
+        let total_price = offer.price.checked_mul(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        let fee = ctx.accounts.config.fee_for(total_price)?;
+
+        // Transfer items from seller to buyer, grossed up so the buyer still
+        // receives `quantity` after any Token-2022 transfer fee
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.seller_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.buyer_token_account.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            with_transfer_fee(&ctx.accounts.item_mint, quantity)?,
+            ctx.accounts.item_mint.decimals,
+        )?;
+
+        // Transfer payment from escrow to seller
+        let (offer_buyer, offer_item_mint) = (offer.buyer, offer.item_mint);
+        let seeds = &[
+            b"offer",
+            offer_buyer.as_ref(),
+            offer_item_mint.as_ref(),
+            &[offer.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let source = PaymentSource::Spl {
+            from: ctx.accounts.escrow_payment_account.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            decimals: ctx.accounts.payment_mint.decimals,
+            authority: offer_info.clone(),
+            token_program: ctx.accounts.payment_token_program.to_account_info(),
+            signer,
+        };
+
+        // Pay creator royalties from escrow before the seller
+        let royalties = pay_royalties(
+            &ctx.accounts.royalty_config,
+            ctx.remaining_accounts,
+            offer.payment_mint,
+            total_price,
+            &source,
+        )?;
+
+        let seller_proceeds = total_price.checked_sub(fee)
+            .and_then(|amount| amount.checked_sub(royalties))
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        pay_fee(&source, Some(&ctx.accounts.fee_vault.to_account_info()), fee)?;
+
+        source.pay(&ctx.accounts.seller_payment_account.to_account_info(), seller_proceeds)?;
+
+        // Update offer
+        offer.quantity = offer.quantity.checked_sub(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        emit!(OfferAccepted {
+            offer: offer.key(),
+            buyer: offer.buyer,
+            seller: ctx.accounts.seller.key(),
+            quantity,
+            total_price,
+            fee,
+            royalties,
+            remaining_quantity: offer.quantity,
+        });
+
+        // A filled offer is closed, refunding anything left in escrow and
+        // both accounts' rent to the buyer so they can offer again
+        if offer.quantity == 0 {
+            offer.active = false;
+            let escrow = &mut ctx.accounts.escrow_payment_account;
+            escrow.reload()?;
+            if escrow.amount > 0 {
+                source.pay(&ctx.accounts.buyer_payment_account.to_account_info(), escrow.amount)?;
+            }
+            harvest_withheld_fees(
+                &ctx.accounts.payment_token_program.to_account_info(),
+                &ctx.accounts.payment_mint.to_account_info(),
+                &escrow.to_account_info(),
+            )?;
+            token_interface::close_account(CpiContext::new_with_signer(
+                ctx.accounts.payment_token_program.to_account_info(),
+                CloseAccount {
+                    account: escrow.to_account_info(),
+                    destination: ctx.accounts.buyer.to_account_info(),
+                    authority: offer_info,
+                },
+                signer,
+            ))?;
+            offer.close(ctx.accounts.buyer.to_account_info())?;
+        }
+
+        msg!("Offer accepted successfully!");
+        Ok(())
+    }
+
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        let offer_info = ctx.accounts.offer.to_account_info();
+        let offer = &ctx.accounts.offer;
+        require!(offer.active, ErrorCode::OfferNotActive);
+
+        // Return everything left in escrow to the buyer, including any tokens
+        // sent to it directly, so it can be closed
+        let remaining = ctx.accounts.escrow_payment_account.amount;
+        let seeds = &[
+            b"offer",
+            offer.buyer.as_ref(),
+            offer.item_mint.as_ref(),
+            &[offer.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow_payment_account.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
+                    to: ctx.accounts.buyer_payment_account.to_account_info(),
+                    authority: offer_info.clone(),
+                },
+                signer,
+            ),
+            remaining,
+            ctx.accounts.payment_mint.decimals,
+        )?;
+
+        // Close the empty escrow; the offer itself is closed to the buyer on exit
+        harvest_withheld_fees(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.payment_mint.to_account_info(),
+            &ctx.accounts.escrow_payment_account.to_account_info(),
+        )?;
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_payment_account.to_account_info(),
+                destination: ctx.accounts.buyer.to_account_info(),
+                authority: offer_info,
+            },
+            signer,
+        ))?;
+
+        emit!(OfferCancelled {
+            offer: offer.key(),
+            buyer: offer.buyer,
+            refunded: remaining,
+        });
+
+        msg!("Offer cancelled successfully!");
+        Ok(())
+    }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFeeBps);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.fee_bps = fee_bps;
+        config.paused = false;
+        config.bump = *ctx.bumps.get("config").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            fee_bps: config.fee_bps,
+            paused: config.paused,
+        });
+
+        msg!("Marketplace config initialized!");
+        Ok(())
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_admin: Option<Pubkey>,
+        fee_bps: Option<u16>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        if let Some(fee_bps) = fee_bps {
+            require!(fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFeeBps);
+            config.fee_bps = fee_bps;
+        }
+        if let Some(new_admin) = new_admin {
+            config.admin = new_admin;
+        }
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            fee_bps: config.fee_bps,
+            paused: config.paused,
+        });
+
+        msg!("Marketplace config updated!");
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.paused = paused;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            fee_bps: config.fee_bps,
+            paused: config.paused,
+        });
+
+        if paused {
+            msg!("Marketplace paused!");
+        } else {
+            msg!("Marketplace unpaused!");
+        }
+        Ok(())
+    }
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config_info = ctx.accounts.config.to_account_info();
+
+        // Only configs smaller than the current layout predate it
+        let legacy = {
+            let data = config_info.try_borrow_data()?;
+            require!(data.len() < MarketplaceConfig::LEN, ErrorCode::ConfigUpToDate);
+            require!(
+                data.len() >= 8 && data[..8] == MarketplaceConfig::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            MarketplaceConfigV0::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(legacy.admin, ctx.accounts.admin.key(), ErrorCode::InvalidAdmin);
+
+        // Admin tops the account up to rent exemption at the new size
+        let rent = Rent::get()?.minimum_balance(MarketplaceConfig::LEN);
+        let shortfall = rent.saturating_sub(config_info.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.admin.to_account_info(),
+                        to: config_info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        config_info.realloc(MarketplaceConfig::LEN, true)?;
+
+        let config = MarketplaceConfig::from(legacy);
+        let mut data = config_info.try_borrow_mut_data()?;
+        config.try_serialize(&mut &mut data[..])?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            fee_bps: config.fee_bps,
+            paused: config.paused,
+        });
+
+        msg!("Marketplace config migrated!");
+        Ok(())
+    }
+
+    pub fn initialize_fee_vault(ctx: Context<InitializeFeeVault>) -> Result<()> {
+        emit!(FeeVaultInitialized {
+            fee_vault: ctx.accounts.fee_vault.key(),
+            payment_mint: ctx.accounts.payment_mint.key(),
+            admin: ctx.accounts.admin.key(),
+        });
+
+        msg!("Fee vault initialized!");
+        Ok(())
+    }
+
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let amount = ctx.accounts.fee_vault.amount;
+
+        // Drain the vault to the admin's token account
+        let seeds = &[b"config".as_ref(), &[config.bump]];
+        let signer = &[&seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.fee_vault.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
+                    to: ctx.accounts.admin_token_account.to_account_info(),
+                    authority: config.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+            ctx.accounts.payment_mint.decimals,
+        )?;
+
+        emit!(FeesWithdrawn {
+            admin: ctx.accounts.admin.key(),
+            payment_mint: ctx.accounts.payment_mint.key(),
+            amount,
+        });
+
+        msg!("Fees withdrawn successfully!");
+        Ok(())
+    }
+
+    pub fn withdraw_native_fees(ctx: Context<WithdrawNativeFees>) -> Result<()> {
+        // Lamport fees accumulate on the config account above its rent-exempt reserve
+        let config_info = ctx.accounts.config.to_account_info();
+        let reserve = Rent::get()?.minimum_balance(config_info.data_len());
+        let amount = config_info.lamports().saturating_sub(reserve);
+
+        **config_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.admin.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(FeesWithdrawn {
+            admin: ctx.accounts.admin.key(),
+            payment_mint: Pubkey::default(),
+            amount,
+        });
+
+        msg!("Native fees withdrawn successfully!");
+        Ok(())
+    }
+
+    pub fn create_royalty_config(
+        ctx: Context<CreateRoyaltyConfig>,
+        creators: Vec<Creator>,
+    ) -> Result<()> {
+        validate_creators(&creators)?;
+
+        let royalty_config = &mut ctx.accounts.royalty_config;
+        royalty_config.item_mint = ctx.accounts.item_mint.key();
+        royalty_config.authority = ctx.accounts.authority.key();
+        royalty_config.creators = creators;
+        royalty_config.bump = *ctx.bumps.get("royalty_config").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+        emit!(RoyaltyConfigUpdated {
+            royalty_config: royalty_config.key(),
+            item_mint: royalty_config.item_mint,
+            creators: royalty_config.creators.clone(),
+        });
+
+        msg!("Royalty config created successfully!");
+        Ok(())
+    }
+
+    pub fn update_royalty_config(
+        ctx: Context<UpdateRoyaltyConfig>,
+        creators: Vec<Creator>,
+    ) -> Result<()> {
+        validate_creators(&creators)?;
+
+        let royalty_config = &mut ctx.accounts.royalty_config;
+        royalty_config.creators = creators;
+
+        emit!(RoyaltyConfigUpdated {
+            royalty_config: royalty_config.key(),
+            item_mint: royalty_config.item_mint,
+            creators: royalty_config.creators.clone(),
+        });
+
+        msg!("Royalty config updated successfully!");
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_auction(
+        ctx: Context<CreateAuction>,
+        reserve_price: u64,
+        min_bid_increment: u64,
+        start_time: i64,
+        end_time: i64,
+        quantity: u64,
+        name: String,
+    ) -> Result<()> {
+        // Validate inputs
+        require!(reserve_price > 0, ErrorCode::InvalidPrice);
+        // A zero increment would let a new bidder take the lead by matching
+        require!(min_bid_increment > 0, ErrorCode::InvalidBidIncrement);
+        require!(quantity > 0, ErrorCode::InvalidQuantity);
+        require!(!name.is_empty(), ErrorCode::InvalidName);
+        require!(name.len() <= MAX_NAME_BYTES, ErrorCode::NameTooLong);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(start_time < end_time, ErrorCode::InvalidAuctionTimes);
+        require!(end_time > now, ErrorCode::InvalidAuctionTimes);
+
+        let auction = &mut ctx.accounts.auction;
+        let seller = &ctx.accounts.seller;
+
+        // Claim the seller's next auction nonce
+        let seller_profile = &mut ctx.accounts.seller_profile;
+        init_seller_profile(seller_profile, seller.key(), ctx.bumps.get("seller_profile"))?;
+        auction.nonce = seller_profile.auction_nonce;
+        seller_profile.auction_nonce = seller_profile.auction_nonce.checked_add(1)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        // Initialize auction account
+        auction.seller = seller.key();
+        auction.item_mint = ctx.accounts.item_mint.key();
+        auction.payment_mint = ctx.accounts.payment_mint.key();
+        auction.name = name;
+        auction.reserve_price = reserve_price;
+        auction.min_bid_increment = min_bid_increment;
+        auction.start_time = start_time;
+        auction.end_time = end_time;
+        auction.highest_bidder = Pubkey::default();
+        auction.highest_bid = 0;
+        auction.active = true;
+        auction.bump = *ctx.bumps.get("auction").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+        // Move items into escrow
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.seller_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.escrow_token_account.to_account_info(),
+                    authority: seller.to_account_info(),
+                },
+            ),
+            quantity,
+            ctx.accounts.item_mint.decimals,
+        )?;
+
+        // Auction what actually arrived, net of any Token-2022 transfer fee
+        ctx.accounts.escrow_token_account.reload()?;
+        auction.quantity = ctx.accounts.escrow_token_account.amount;
+        require!(auction.quantity > 0, ErrorCode::InvalidQuantity);
+
+        emit!(AuctionCreated {
+            auction: auction.key(),
+            seller: auction.seller,
+            item_mint: auction.item_mint,
+            payment_mint: auction.payment_mint,
+            reserve_price,
+            min_bid_increment,
+            start_time,
+            end_time,
+            quantity: auction.quantity,
+        });
+
+        msg!("Auction created successfully!");
+        Ok(())
+    }
+
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+        let auction_info = ctx.accounts.auction.to_account_info();
+        let auction = &mut ctx.accounts.auction;
+        require!(auction.active, ErrorCode::AuctionNotActive);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= auction.start_time, ErrorCode::AuctionNotStarted);
+        require!(now < auction.end_time, ErrorCode::AuctionEnded);
+
+        let min_bid = if auction.has_bids() {
+            auction.highest_bid.checked_add(auction.min_bid_increment)
+                .ok_or(ErrorCode::NumericalOverflow)?
+        } else {
+            auction.reserve_price
+        };
+        require!(amount >= min_bid, ErrorCode::BidTooLow);
+
+        // Escrow the new bid, grossed up so the escrow still holds `amount`
+        // after any Token-2022 transfer fee
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.bidder_payment_account.to_account_info(),
+                    mint: ctx.accounts.payment_mint.to_account_info(),
+                    to: ctx.accounts.bid_escrow_account.to_account_info(),
+                    authority: ctx.accounts.bidder.to_account_info(),
+                },
+            ),
+            with_transfer_fee(&ctx.accounts.payment_mint, amount)?,
+            ctx.accounts.payment_mint.decimals,
+        )?;
+
+        // Refund the previous highest bidder
+        if auction.has_bids() {
+            let previous_bidder_account = ctx.accounts.previous_bidder_payment_account
+                .as_ref()
+                .ok_or(ErrorCode::MissingPreviousBidderAccount)?;
+            require_keys_eq!(
+                previous_bidder_account.owner,
+                auction.highest_bidder,
+                ErrorCode::MissingPreviousBidderAccount
+            );
+
+            let nonce = auction.nonce.to_le_bytes();
+            let seeds = &[
+                b"auction",
+                auction.seller.as_ref(),
+                nonce.as_ref(),
+                &[auction.bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.bid_escrow_account.to_account_info(),
+                        mint: ctx.accounts.payment_mint.to_account_info(),
+                        to: previous_bidder_account.to_account_info(),
+                        authority: auction_info,
+                    },
+                    signer,
+                ),
+                auction.highest_bid,
+                ctx.accounts.payment_mint.decimals,
+            )?;
+        }
+
+        // Record the new highest bid
+        let previous_bidder = auction.highest_bidder;
+        auction.highest_bidder = ctx.accounts.bidder.key();
+        auction.highest_bid = amount;
+
+        emit!(BidPlaced {
+            auction: auction.key(),
+            bidder: auction.highest_bidder,
+            amount,
+            previous_bidder,
+        });
+
+        msg!("Bid placed successfully!");
+        Ok(())
+    }
+
+    pub fn settle_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>,
+    ) -> Result<()> {
+        let auction_info = ctx.accounts.auction.to_account_info();
+        let auction = &ctx.accounts.auction;
+        require!(auction.active, ErrorCode::AuctionNotActive);
+        require!(auction.has_bids(), ErrorCode::NoBids);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= auction.end_time, ErrorCode::AuctionNotEnded);
+
+        let total_price = auction.highest_bid;
+        let fee = ctx.accounts.config.fee_for(total_price)?;
+
+        let nonce = auction.nonce.to_le_bytes();
+        let seeds = &[
+            b"auction",
+            auction.seller.as_ref(),
+            nonce.as_ref(),
+            &[auction.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Transfer items from escrow to the winner, including any tokens sent
+        // to the escrow directly, so it can be closed
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.winner_token_account.to_account_info(),
+                    authority: auction_info.clone(),
+                },
+                signer,
+            ),
+            ctx.accounts.escrow_token_account.amount,
+            ctx.accounts.item_mint.decimals,
+        )?;
+
+        let source = PaymentSource::Spl {
+            from: ctx.accounts.bid_escrow_account.to_account_info(),
+            mint: ctx.accounts.payment_mint.to_account_info(),
+            decimals: ctx.accounts.payment_mint.decimals,
+            authority: auction_info.clone(),
+            token_program: ctx.accounts.payment_token_program.to_account_info(),
+            signer,
+        };
+
+        // Pay creator royalties from the winning bid before the seller
+        let royalties = pay_royalties(
+            &ctx.accounts.royalty_config,
+            ctx.remaining_accounts,
+            auction.payment_mint,
+            total_price,
+            &source,
+        )?;
+
+        // The seller also gets any tokens sent to the bid escrow directly, so
+        // it can be closed
+        let seller_proceeds = ctx.accounts.bid_escrow_account.amount.checked_sub(fee)
+            .and_then(|amount| amount.checked_sub(royalties))
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        pay_fee(&source, Some(&ctx.accounts.fee_vault.to_account_info()), fee)?;
+
+        source.pay(&ctx.accounts.seller_payment_account.to_account_info(), seller_proceeds)?;
+
+        // Close both empty escrows; the auction itself is closed to the seller on exit
+        close_escrow(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.escrow_token_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            &auction_info,
+            signer,
+        )?;
+        close_escrow(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_mint.to_account_info(),
+            &ctx.accounts.bid_escrow_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            &auction_info,
+            signer,
+        )?;
+
+        emit!(AuctionSettled {
+            auction: auction.key(),
+            seller: auction.seller,
+            winner: auction.highest_bidder,
+            winning_bid: total_price,
+            fee,
+            royalties,
+        });
+
+        msg!("Auction settled successfully!");
+        Ok(())
+    }
+
+    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+        let auction_info = ctx.accounts.auction.to_account_info();
+        let auction = &mut ctx.accounts.auction;
+        require!(auction.active, ErrorCode::AuctionNotActive);
+        require!(!auction.has_bids(), ErrorCode::AuctionHasBids);
+
+        // Return everything in escrow to the seller, including any tokens sent
+        // to it directly, so it can be closed
+        let nonce = auction.nonce.to_le_bytes();
+        let seeds = &[
+            b"auction",
+            auction.seller.as_ref(),
+            nonce.as_ref(),
+            &[auction.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    mint: ctx.accounts.item_mint.to_account_info(),
+                    to: ctx.accounts.seller_token_account.to_account_info(),
+                    authority: auction_info.clone(),
+                },
+                signer,
+            ),
+            ctx.accounts.escrow_token_account.amount,
+            ctx.accounts.item_mint.decimals,
+        )?;
+
+        // Nobody bid, so the bid escrow only holds tokens sent to it directly
+        let stray = ctx.accounts.bid_escrow_account.amount;
+        if stray > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.payment_token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.bid_escrow_account.to_account_info(),
+                        mint: ctx.accounts.payment_mint.to_account_info(),
+                        to: ctx.accounts.seller_payment_account.to_account_info(),
+                        authority: auction_info.clone(),
+                    },
+                    signer,
+                ),
+                stray,
+                ctx.accounts.payment_mint.decimals,
+            )?;
+        }
+
+        // Close both empty escrows; the auction itself is closed to the seller on exit
+        close_escrow(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.item_mint.to_account_info(),
+            &ctx.accounts.escrow_token_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            &auction_info,
+            signer,
+        )?;
+        close_escrow(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_mint.to_account_info(),
+            &ctx.accounts.bid_escrow_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            &auction_info,
+            signer,
+        )?;
+
+        emit!(AuctionCancelled {
+            auction: auction.key(),
+            seller: auction.seller,
+        });
+
+        msg!("Auction cancelled successfully!");
+        Ok(())
+    }
+}
+
+fn validate_creators(creators: &[Creator]) -> Result<()> {
+    require!(creators.len() <= MAX_CREATORS, ErrorCode::TooManyCreators);
+
+    let mut total_bps: u16 = 0;
+    for creator in creators {
+        require!(creator.share_bps > 0, ErrorCode::InvalidRoyaltyBps);
+        total_bps = total_bps.checked_add(creator.share_bps)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+    }
+    require!(total_bps <= MAX_ROYALTY_BPS, ErrorCode::InvalidRoyaltyBps);
+
+    Ok(())
+}
+
+/// Creates a listing for `create_listing` or, when `swap` is set,
+/// `create_swap_listing`.
+#[allow(clippy::too_many_arguments)]
+fn open_listing(
+    ctx: Context<CreateListing>,
+    price: u64,
+    quantity: u64,
+    name: String,
+    dutch_auction: Option<DutchAuction>,
+    expires_at: Option<i64>,
+    buyer_restriction: Option<BuyerRestriction>,
+    max_per_buyer: Option<u64>,
+    metadata: ListingMetadata,
+    swap: bool,
+) -> Result<()> {
+    // Validate inputs
+    require!(price > 0, ErrorCode::InvalidPrice);
+    require!(quantity > 0, ErrorCode::InvalidQuantity);
+    require!(!name.is_empty(), ErrorCode::InvalidName);
+    require!(name.len() <= MAX_NAME_BYTES, ErrorCode::NameTooLong);
+    metadata.validate()?;
+    if let Some(dutch_auction) = &dutch_auction {
+        require!(dutch_auction.floor_price > 0, ErrorCode::InvalidPrice);
+        require!(dutch_auction.floor_price < price, ErrorCode::InvalidDutchAuction);
+        require!(dutch_auction.duration > 0, ErrorCode::InvalidDutchAuction);
+    }
+    if let Some(expires_at) = expires_at {
+        require!(expires_at > Clock::get()?.unix_timestamp, ErrorCode::InvalidExpiry);
+    }
+    if let Some(max_per_buyer) = max_per_buyer {
+        require!(max_per_buyer > 0, ErrorCode::InvalidQuantity);
+    }
+
+    let listing = &mut ctx.accounts.listing;
+    let seller = &ctx.accounts.seller;
+
+    // Claim the seller's next listing nonce
+    let seller_profile = &mut ctx.accounts.seller_profile;
+    init_seller_profile(seller_profile, seller.key(), ctx.bumps.get("seller_profile"))?;
+    listing.nonce = seller_profile.listing_nonce;
+    seller_profile.listing_nonce = seller_profile.listing_nonce.checked_add(1)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+
+    // Initialize listing account
+    listing.version = LISTING_VERSION;
+    listing.seller = seller.key();
+    // Listings without a payment mint are priced in lamports
+    match (&ctx.accounts.payment_mint, swap) {
+        (Some(payment_mint), false) => {
+            listing.payment_kind = PaymentKind::Spl;
+            listing.payment_mint = payment_mint.key();
+        }
+        (Some(payment_mint), true) => {
+            require_keys_neq!(payment_mint.key(), ctx.accounts.item_mint.key(), ErrorCode::InvalidSwapMint);
+            listing.payment_kind = PaymentKind::Swap;
+            listing.payment_mint = payment_mint.key();
+        }
+        (None, false) => {
+            listing.payment_kind = PaymentKind::Native;
+            listing.payment_mint = Pubkey::default();
+        }
+        (None, true) => return err!(ErrorCode::MissingPaymentAccount),
+    }
+
+    listing.price = price;
+    listing.name = name;
+    listing.metadata = metadata;
+    listing.dutch_auction = dutch_auction;
+    listing.expires_at = expires_at;
+    listing.buyer_restriction = buyer_restriction;
+    listing.max_per_buyer = max_per_buyer;
+    listing.active = true;
+    listing.bump = *ctx.bumps.get("listing").ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+    // Initialize escrow account
+    token_interface::transfer_checked(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.seller_token_account.to_account_info(),
+                mint: ctx.accounts.item_mint.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: seller.to_account_info(),
+            },
+        ),
+        quantity,
+        ctx.accounts.item_mint.decimals,
+    )?;
+
+    // List what actually arrived, net of any Token-2022 transfer fee
+    ctx.accounts.escrow_token_account.reload()?;
+    listing.quantity = ctx.accounts.escrow_token_account.amount;
+    require!(listing.quantity > 0, ErrorCode::InvalidQuantity);
+
+    emit!(ListingCreated {
+        listing: listing.key(),
+        seller: listing.seller,
+        nonce: listing.nonce,
+        item_mint: ctx.accounts.item_mint.key(),
+        payment_kind: listing.payment_kind,
+        payment_mint: listing.payment_mint,
+        price: listing.price,
+        quantity: listing.quantity,
+    });
+
+    msg!("Listing created successfully!");
+    Ok(())
+}
+
+/// Fills in `seller_profile` the first time one of the seller's listings or
+/// auctions creates it.
+fn init_seller_profile(
+    seller_profile: &mut Account<SellerProfile>,
+    seller: Pubkey,
+    bump: Option<&u8>,
+) -> Result<()> {
+    if seller_profile.seller == Pubkey::default() {
+        seller_profile.seller = seller;
+        seller_profile.bump = *bump.ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+
+        emit!(SellerProfileCreated {
+            seller_profile: seller_profile.key(),
+            seller,
+        });
+    }
+    Ok(())
+}
+
+/// Pays the protocol fee out of `source` into `fee_vault`. The vault is
+/// only needed when there is a fee to pay, so zero-fee sales go through in
+/// mints nobody has created a fee vault for.
+fn pay_fee<'info>(
+    source: &PaymentSource<'_, 'info>,
+    fee_vault: Option<&AccountInfo<'info>>,
+    fee: u64,
+) -> Result<()> {
+    if fee == 0 {
+        return Ok(());
+    }
+    let fee_vault = fee_vault.ok_or(ErrorCode::MissingFeeVault)?;
+    require!(!fee_vault.data_is_empty(), ErrorCode::MissingFeeVault);
+    source.pay(fee_vault, fee)
+}
+
+/// Adds `quantity` to the buyer's receipt for `listing`, filling it in on
+/// first use, and enforces the listing's per-buyer cap.
+fn track_buyer_receipt(
+    buyer_receipt: &mut Option<Account<BuyerReceipt>>,
+    bump: Option<&u8>,
+    listing: &Account<Listing>,
+    buyer: Pubkey,
+    quantity: u64,
+) -> Result<()> {
+    let buyer_receipt = match buyer_receipt {
+        Some(buyer_receipt) => buyer_receipt,
+        None => {
+            require!(listing.max_per_buyer.is_none(), ErrorCode::MissingBuyerReceipt);
+            return Ok(());
+        }
+    };
+
+    if buyer_receipt.buyer == Pubkey::default() {
+        buyer_receipt.listing = listing.key();
+        buyer_receipt.buyer = buyer;
+        buyer_receipt.bump = *bump.ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+    }
+    buyer_receipt.quantity = buyer_receipt.quantity.checked_add(quantity)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    if let Some(max_per_buyer) = listing.max_per_buyer {
+        require!(buyer_receipt.quantity <= max_per_buyer, ErrorCode::BuyerLimitExceeded);
+    }
+    Ok(())
+}
+
+/// Records the sale on `sale_receipt` when the buyer asked for one.
+fn record_sale_receipt(
+    sale_receipt: &mut Option<Account<SaleReceipt>>,
+    bump: Option<&u8>,
+    listing: Pubkey,
+    buyer: Pubkey,
+    quantity: u64,
+    unit_price: u64,
+) -> Result<()> {
+    let sale_receipt = match sale_receipt {
+        Some(sale_receipt) => sale_receipt,
+        None => return Ok(()),
+    };
+
+    let clock = Clock::get()?;
+    sale_receipt.listing = listing;
+    sale_receipt.buyer = buyer;
+    sale_receipt.quantity = quantity;
+    sale_receipt.unit_price = unit_price;
+    sale_receipt.timestamp = clock.unix_timestamp;
+    sale_receipt.slot = clock.slot;
+    sale_receipt.bump = *bump.ok_or(ErrorCode::BumpSeedNotInHashMap)?;
+    Ok(())
+}
+
+/// Where the proceeds of a sale are paid from.
+enum PaymentSource<'a, 'info> {
+    /// SPL tokens of `mint` moved out of `from` by `authority`, signed with
+    /// `signer` seeds when the authority is a PDA.
+    Spl {
+        from: AccountInfo<'info>,
+        mint: AccountInfo<'info>,
+        decimals: u8,
+        authority: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        signer: &'a [&'a [&'a [u8]]],
+    },
+    /// Lamports moved out of a system-owned signer.
+    Native {
+        from: AccountInfo<'info>,
+        system_program: AccountInfo<'info>,
+    },
+}
+
+impl<'info> PaymentSource<'_, 'info> {
+    fn pay(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        match self {
+            PaymentSource::Spl { from, mint, decimals, authority, token_program, signer } => {
+                token_interface::transfer_checked(
+                    CpiContext::new_with_signer(
+                        token_program.clone(),
+                        TransferChecked {
+                            from: from.clone(),
+                            mint: mint.clone(),
+                            to: to.clone(),
+                            authority: authority.clone(),
+                        },
+                        signer,
+                    ),
+                    amount,
+                    *decimals,
+                )
+            }
+            PaymentSource::Native { from, system_program } => system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: from.clone(),
+                        to: to.clone(),
+                    },
+                ),
+                amount,
+            ),
+        }
+    }
+}
+
+/// A `purchase_batch` payment account, which like an absent optional
+/// account is the program id when the listing is priced in lamports.
+fn batch_payment_account<'a, 'info>(account: &'a AccountInfo<'info>) -> Result<&'a AccountInfo<'info>> {
+    require_keys_neq!(account.key(), crate::ID, ErrorCode::MissingPaymentAccount);
+    Ok(account)
+}
+
+/// Creates the program-owned PDA `account` with `space` bytes, paid for by
+/// `payer`, the way Anchor's `init` does for accounts that are only known at
+/// runtime. An address someone already sent lamports to is topped up and
+/// claimed instead.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer: &[&[&[u8]]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+    if lamports == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+                signer,
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    if rent > lamports {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            rent - lamports,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            signer,
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            signer,
+        ),
+        &crate::ID,
+    )
+}
+
+fn required<'info, T: ToAccountInfo<'info>>(account: &Option<T>) -> Result<AccountInfo<'info>> {
+    account
+        .as_ref()
+        .map(|account| account.to_account_info())
+        .ok_or_else(|| error!(ErrorCode::MissingPaymentAccount))
+}
+
+/// Pays every creator on the item's royalty config, if one exists, out of
+/// `source`. Creator payment accounts (token accounts for SPL, wallets for
+/// lamports) are expected in `creator_accounts` in the same order as
+/// `RoyaltyConfig::creators`. Returns the total paid.
+fn pay_royalties<'info>(
+    royalty_config: &AccountInfo<'info>,
+    creator_accounts: &[AccountInfo<'info>],
+    payment_mint: Pubkey,
+    total_price: u64,
+    source: &PaymentSource<'_, 'info>,
+) -> Result<u64> {
+    // No royalty config for this mint means no royalties are owed
+    if royalty_config.data_is_empty() {
+        return Ok(0);
+    }
+    require_keys_eq!(*royalty_config.owner, crate::ID, ErrorCode::InvalidRoyaltyConfig);
+    let config = RoyaltyConfig::try_deserialize(&mut &royalty_config.data.borrow()[..])?;
+
+    require!(
+        creator_accounts.len() >= config.creators.len(),
+        ErrorCode::MissingCreatorAccount
+    );
+
+    let mut paid: u64 = 0;
+    for (creator, account_info) in config.creators.iter().zip(creator_accounts) {
+        match source {
+            PaymentSource::Spl { .. } => {
+                let creator_account = InterfaceAccount::<TokenAccount>::try_from(account_info)?;
+                require_keys_eq!(creator_account.owner, creator.address, ErrorCode::MissingCreatorAccount);
+                require_keys_eq!(creator_account.mint, payment_mint, ErrorCode::InvalidMint);
+            }
+            PaymentSource::Native { .. } => {
+                require_keys_eq!(account_info.key(), creator.address, ErrorCode::MissingCreatorAccount);
+            }
+        }
+
+        let amount = bps_of(total_price, creator.share_bps)?;
+        if amount == 0 {
+            continue;
+        }
+
+        source.pay(account_info, amount)?;
+
+        paid = paid.checked_add(amount).ok_or(ErrorCode::NumericalOverflow)?;
+    }
+
+    Ok(paid)
+}
+
+/// Amount to send so that `net_amount` arrives after the mint's Token-2022
+/// transfer fee, if it has one.
+fn with_transfer_fee(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != anchor_spl::token_2022::ID {
+        return Ok(net_amount);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+    let fee = match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+            .ok_or(ErrorCode::NumericalOverflow)?,
+        Err(_) => 0,
+    };
+
+    Ok(net_amount.checked_add(fee).ok_or(ErrorCode::NumericalOverflow)?)
+}
+
+/// Sweeps any Token-2022 transfer fees withheld in `account` to the mint;
+/// token accounts holding withheld fees cannot be closed.
+fn harvest_withheld_fees<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+) -> Result<()> {
+    if *account.owner != anchor_spl::token_2022::ID {
+        return Ok(());
+    }
+
+    let withheld = {
+        let data = account.try_borrow_data()?;
+        let account_state = StateWithExtensions::<TokenAccountState>::unpack(&data)?;
+        account_state.get_extension::<TransferFeeAmount>()
+            .map_or(0, |transfer_fee_amount| u64::from(transfer_fee_amount.withheld_amount))
+    };
+    if withheld == 0 {
+        return Ok(());
+    }
+
+    let ix = harvest_withheld_tokens_to_mint(token_program.key, mint.key, &[account.key])?;
+    invoke(&ix, &[mint.clone(), account.clone(), token_program.clone()])?;
+    Ok(())
+}
+
+/// Closes the program-owned `account` to `destination` the way Anchor's
+/// `close` constraint does, for accounts only readable in an older layout.
+fn close_legacy_account<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports = destination.lamports().checked_add(account.lamports())
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    **destination.lamports.borrow_mut() = lamports;
+    **account.lamports.borrow_mut() = 0;
+
+    account.assign(&system_program::ID);
+    account.realloc(0, false)?;
+    Ok(())
+}
+
+/// Closes the empty escrow `account`, owned by the PDA `authority`, to
+/// `destination`, harvesting any withheld Token-2022 fees to the mint first.
+fn close_escrow<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+) -> Result<()> {
+    harvest_withheld_fees(token_program, mint, account)?;
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.clone(),
+        CloseAccount {
+            account: account.clone(),
+            destination: destination.clone(),
+            authority: authority.clone(),
+        },
+        signer,
+    ))
+}
+
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(ErrorCode::NumericalOverflow)?
+        .checked_div(BPS_DENOMINATOR as u128)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    Ok(u64::try_from(value).map_err(|_| ErrorCode::NumericalOverflow)?)
+}
+
+#[derive(Accounts)]
+pub struct CreateListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = SellerProfile::LEN,
+        seeds = [b"seller_profile", seller.key().as_ref()],
+        bump
+    )]
+    pub seller_profile: Account<'info, SellerProfile>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+    
+    #[account(
+        init,
+        payer = seller,
+        space = Listing::LEN,
+        seeds = [b"listing", seller.key().as_ref(), seller_profile.listing_nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+    
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"escrow", listing.key().as_ref()],
+        bump,
+        token::mint = item_mint,
+        token::authority = listing,
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub item_mint: InterfaceAccount<'info, Mint>,
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(quantity: u64, max_price: u64, max_total: u64)]
+pub struct Purchase<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    /// CHECK: This is the seller's address from the listing
+    #[account(
+        mut,
+        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = buyer_payment_account.mint == listing.payment_mint @ ErrorCode::InvalidBuyerPaymentMint
+    )]
+    pub buyer_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        constraint = seller_payment_account.mint == listing.payment_mint @ ErrorCode::InvalidSellerPaymentMint,
+        constraint = seller_payment_account.owner == listing.seller @ ErrorCode::InvalidSellerPaymentOwner
+    )]
+    pub seller_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = payment_mint.key() == listing.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Fee vault PDA for the payment mint; only needs to exist when
+    /// the sale owes a fee, checked by `pay_fee`
+    #[account(
+        mut,
+        seeds = [b"fee_vault", listing.payment_mint.as_ref()],
+        bump,
+    )]
+    pub fee_vault: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Royalty config PDA for the item mint; may be uninitialized
+    #[account(
+        seeds = [b"royalty", escrow_token_account.mint.as_ref()],
+        bump,
+    )]
+    pub royalty_config: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = BuyerReceipt::LEN,
+        seeds = [b"buyer_receipt", listing.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub buyer_receipt: Option<Account<'info, BuyerReceipt>>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = SaleReceipt::LEN,
+        seeds = [b"sale_receipt", listing.key().as_ref(), listing.sale_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub sale_receipt: Option<Account<'info, SaleReceipt>>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseBatch<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+        constraint = listing.payment_kind == PaymentKind::Swap @ ErrorCode::NotASwapListing
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key() @ ErrorCode::InvalidOwner
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The buyer's tokens of the mint the listing is priced in
+    #[account(
+        mut,
+        constraint = buyer_payment_account.mint == listing.payment_mint @ ErrorCode::InvalidBuyerPaymentMint
+    )]
+    pub buyer_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.mint == listing.payment_mint @ ErrorCode::InvalidSellerPaymentMint,
+        constraint = seller_payment_account.owner == listing.seller @ ErrorCode::InvalidSellerPaymentOwner
+    )]
+    pub seller_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = payment_mint.key() == listing.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = BuyerReceipt::LEN,
+        seeds = [b"buyer_receipt", listing.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub buyer_receipt: Option<Account<'info, BuyerReceipt>>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = SaleReceipt::LEN,
+        seeds = [b"sale_receipt", listing.key().as_ref(), listing.sale_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub sale_receipt: Option<Account<'info, SaleReceipt>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelListing<'info> {
+    #[account(
+        mut,
+        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+        close = seller,
+    )]
+    pub listing: Account<'info, Listing>,
+    
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireListing<'info> {
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: This is the seller's address from the listing
+    #[account(
+        mut,
+        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+        close = seller,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == listing.seller @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseListing<'info> {
+    #[account(
+        mut,
+        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+        close = seller,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives any tokens sent to the escrow after the listing emptied
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(price: Option<u64>, deposit: u64, withdraw: u64)]
+pub struct UpdateListing<'info> {
+    #[account(
+        constraint = seller.key() == listing.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", listing.seller.as_ref(), listing.nonce.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == listing.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = item_mint.key() == escrow_token_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = SellerProfile::LEN,
+        seeds = [b"seller_profile", seller.key().as_ref()],
+        bump
+    )]
+    pub seller_profile: Account<'info, SellerProfile>,
+
+    /// CHECK: Listing in the original layout; validated and closed by the instruction
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    pub legacy_listing: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", legacy_listing.key().as_ref()],
+        bump,
+    )]
+    pub legacy_escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = Listing::LEN,
+        seeds = [b"listing", seller.key().as_ref(), seller_profile.listing_nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"escrow", listing.key().as_ref()],
+        bump,
+        token::mint = item_mint,
+        token::authority = listing,
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = item_mint.key() == legacy_escrow_account.mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateOffer<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = Offer::LEN,
+        seeds = [b"offer", buyer.key().as_ref(), item_mint.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        constraint = buyer_payment_account.owner == buyer.key() @ ErrorCode::InvalidOwner,
+        constraint = buyer_payment_account.mint == payment_mint.key() @ ErrorCode::InvalidMint
+    )]
+    pub buyer_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = buyer,
+        seeds = [b"offer_escrow", offer.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = offer,
+    )]
+    pub escrow_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub item_mint: InterfaceAccount<'info, Mint>,
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(quantity: u64)]
+pub struct AcceptOffer<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// CHECK: This is the buyer's address from the offer; it receives the
+    /// offer's rent once the offer is filled
+    #[account(
+        mut,
+        constraint = buyer.key() == offer.buyer @ ErrorCode::InvalidBuyer
+    )]
+    pub buyer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"offer", offer.buyer.as_ref(), offer.item_mint.as_ref()],
+        bump = offer.bump,
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        constraint = escrow_payment_account.owner == offer.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner,
+        constraint = seller_token_account.mint == offer.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == offer.buyer @ ErrorCode::InvalidOwner,
+        constraint = buyer_token_account.mint == offer.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.mint == offer.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub seller_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Refunded whatever is left in escrow when the offer is filled
+    #[account(
+        mut,
+        constraint = buyer_payment_account.owner == offer.buyer @ ErrorCode::InvalidOwner,
+        constraint = buyer_payment_account.mint == offer.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub buyer_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Fee vault PDA for the payment mint; only needs to exist when
+    /// the sale owes a fee, checked by `pay_fee`
+    #[account(
+        mut,
+        seeds = [b"fee_vault", offer.payment_mint.as_ref()],
+        bump,
+    )]
+    pub fee_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Royalty config PDA for the item mint; may be uninitialized
+    #[account(
+        seeds = [b"royalty", offer.item_mint.as_ref()],
+        bump,
+    )]
+    pub royalty_config: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = item_mint.key() == offer.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = payment_mint.key() == offer.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(
+        mut,
+        constraint = buyer.key() == offer.buyer @ ErrorCode::InvalidBuyer
+    )]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"offer", offer.buyer.as_ref(), offer.item_mint.as_ref()],
+        bump = offer.bump,
+        close = buyer,
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        constraint = escrow_payment_account.owner == offer.key() @ ErrorCode::InvalidEscrowOwner
+    )]
+    pub escrow_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_payment_account.owner == buyer.key() @ ErrorCode::InvalidOwner
+    )]
+    pub buyer_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = payment_mint.key() == offer.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = MarketplaceConfig::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        constraint = admin.key() == config.admin @ ErrorCode::InvalidAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        constraint = admin.key() == config.admin @ ErrorCode::InvalidAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Config in an older layout; validated and rewritten by the instruction
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        owner = crate::ID,
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeVault<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == config.admin @ ErrorCode::InvalidAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"fee_vault", payment_mint.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = config,
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(
+        constraint = admin.key() == config.admin @ ErrorCode::InvalidAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", fee_vault.mint.as_ref()],
+        bump,
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = admin_token_account.owner == admin.key() @ ErrorCode::InvalidOwner
+    )]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = payment_mint.key() == fee_vault.mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawNativeFees<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == config.admin @ ErrorCode::InvalidAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRoyaltyConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = RoyaltyConfig::LEN,
+        seeds = [b"royalty", item_mint.key().as_ref()],
+        bump
+    )]
+    pub royalty_config: Account<'info, RoyaltyConfig>,
+
+    #[account(
+        constraint = item_mint.mint_authority == COption::Some(authority.key()) @ ErrorCode::InvalidRoyaltyAuthority
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRoyaltyConfig<'info> {
+    #[account(
+        constraint = authority.key() == royalty_config.authority @ ErrorCode::InvalidRoyaltyAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"royalty", royalty_config.item_mint.as_ref()],
+        bump = royalty_config.bump,
+    )]
+    pub royalty_config: Account<'info, RoyaltyConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    reserve_price: u64,
+    min_bid_increment: u64,
+    start_time: i64,
+    end_time: i64,
+    quantity: u64,
+    name: String
+)]
+pub struct CreateAuction<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = SellerProfile::LEN,
+        seeds = [b"seller_profile", seller.key().as_ref()],
+        bump
+    )]
+    pub seller_profile: Account<'info, SellerProfile>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = Auction::LEN,
+        seeds = [b"auction", seller.key().as_ref(), seller_profile.auction_nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"escrow", auction.key().as_ref()],
+        bump,
+        token::mint = item_mint,
+        token::authority = auction,
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"bid_escrow", auction.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = auction,
+        token::token_program = payment_token_program,
+    )]
+    pub bid_escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub item_mint: InterfaceAccount<'info, Mint>,
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", auction.seller.as_ref(), auction.nonce.to_le_bytes().as_ref()],
+        bump = auction.bump,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_escrow", auction.key().as_ref()],
+        bump,
+    )]
+    pub bid_escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = bidder_payment_account.owner == bidder.key() @ ErrorCode::InvalidOwner,
+        constraint = bidder_payment_account.mint == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub bidder_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub previous_bidder_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        constraint = payment_mint.key() == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ ErrorCode::MarketplacePaused
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    /// CHECK: This is the seller's address from the auction
+    #[account(
+        mut,
+        constraint = seller.key() == auction.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", auction.seller.as_ref(), auction.nonce.to_le_bytes().as_ref()],
+        bump = auction.bump,
+        close = seller,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", auction.key().as_ref()],
+        bump,
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_escrow", auction.key().as_ref()],
+        bump,
+    )]
+    pub bid_escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = winner_token_account.owner == auction.highest_bidder @ ErrorCode::InvalidOwner,
+        constraint = winner_token_account.mint == auction.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub winner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.owner == auction.seller @ ErrorCode::InvalidOwner,
+        constraint = seller_payment_account.mint == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub seller_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    // Settling stays open while paused so winning bids can always be paid out
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Fee vault PDA for the payment mint; only needs to exist when
+    /// the sale owes a fee, checked by `pay_fee`
+    #[account(
+        mut,
+        seeds = [b"fee_vault", auction.payment_mint.as_ref()],
+        bump,
+    )]
+    pub fee_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Royalty config PDA for the item mint; may be uninitialized
+    #[account(
+        seeds = [b"royalty", auction.item_mint.as_ref()],
+        bump,
+    )]
+    pub royalty_config: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = item_mint.key() == auction.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = payment_mint.key() == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(
+        mut,
+        constraint = seller.key() == auction.seller @ ErrorCode::InvalidSeller
+    )]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", auction.seller.as_ref(), auction.nonce.to_le_bytes().as_ref()],
+        bump = auction.bump,
+        close = seller,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", auction.key().as_ref()],
+        bump,
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_escrow", auction.key().as_ref()],
+        bump,
+    )]
+    pub bid_escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::InvalidOwner
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.owner == seller.key() @ ErrorCode::InvalidOwner,
+        constraint = seller_payment_account.mint == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub seller_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = item_mint.key() == auction.item_mint @ ErrorCode::InvalidMint
+    )]
+    pub item_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = payment_mint.key() == auction.payment_mint @ ErrorCode::InvalidMint
+    )]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Interface<'info, TokenInterface>,
+}
+
+#[account]
+pub struct Listing {
+    /// Layout version, always `LISTING_VERSION` for current accounts.
+    pub version: u8,
+    pub seller: Pubkey,
+    /// Seller profile nonce this listing's address is derived from.
+    pub nonce: u64,
+    pub payment_kind: PaymentKind,
+    pub payment_mint: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub name: String,
+    pub metadata: ListingMetadata,
+    pub dutch_auction: Option<DutchAuction>,
+    pub expires_at: Option<i64>,
+    pub buyer_restriction: Option<BuyerRestriction>,
+    pub max_per_buyer: Option<u64>,
+    pub sale_count: u64,
+    pub units_sold: u64,
+    pub total_volume: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Listing {
+    /// Size of a current-version listing account; legacy accounts are smaller.
+    pub const LEN: usize = 8 + // discriminator
+        1 +  // version
+        32 + // seller pubkey
+        8 +  // nonce
+        1 +  // payment kind
+        32 + // payment mint
+        8 +  // price
+        8 +  // quantity
+        4 + MAX_NAME_BYTES + // name (String of at most MAX_NAME_BYTES UTF-8 bytes)
+        ListingMetadata::LEN + // metadata
+        1 + DutchAuction::LEN + // dutch auction (Option)
+        1 + 8 + // expires at (Option)
+        1 + BuyerRestriction::LEN + // buyer restriction (Option)
+        1 + 8 + // max per buyer (Option)
+        8 +  // sale count
+        8 +  // units sold
+        8 +  // total volume
+        1 +  // active
+        1;   // bump
+
+    /// Whether the listing has an expiry and `now` is at or past it.
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Checks that `buyer` may buy `quantity` items at `now` for at most
+    /// `max_price` each, returning the unit price they pay.
+    pub fn quote(
+        &self,
+        buyer: &Pubkey,
+        quantity: u64,
+        max_price: u64,
+        proof: &[[u8; 32]],
+        now: i64,
+    ) -> Result<u64> {
+        require!(self.version == LISTING_VERSION, ErrorCode::ListingNeedsMigration);
+        require!(self.active, ErrorCode::ListingNotActive);
+        if let Some(buyer_restriction) = &self.buyer_restriction {
+            require!(buyer_restriction.allows(buyer, proof), ErrorCode::BuyerNotAllowed);
+        }
+        require!(quantity > 0, ErrorCode::InvalidQuantity);
+        require!(quantity <= self.quantity, ErrorCode::InsufficientQuantity);
+        require!(!self.is_expired(now), ErrorCode::ListingExpired);
+
+        let unit_price = self.current_price(now)?;
+        require!(unit_price <= max_price, ErrorCode::PriceMismatch);
+        Ok(unit_price)
+    }
+
+    /// Books a sale of `quantity` items for `total_price`, deactivating the
+    /// listing once it sells out.
+    pub fn record_sale(&mut self, quantity: u64, total_price: u64) -> Result<()> {
+        self.quantity = self.quantity.checked_sub(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        self.sale_count = self.sale_count.checked_add(1)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        self.units_sold = self.units_sold.checked_add(quantity)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        self.total_volume = self.total_volume.checked_add(total_price)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        if self.quantity == 0 {
+            self.active = false;
+        }
+        Ok(())
+    }
+
+    /// Unit price at `now`. Fixed-price listings always sell at `price`;
+    /// Dutch auctions decay linearly from `price` to the floor price.
+    pub fn current_price(&self, now: i64) -> Result<u64> {
+        let dutch_auction = match &self.dutch_auction {
+            Some(dutch_auction) => dutch_auction,
+            None => return Ok(self.price),
+        };
+
+        let elapsed = now.saturating_sub(dutch_auction.start_time);
+        if elapsed <= 0 {
+            return Ok(self.price);
+        }
+        if elapsed >= dutch_auction.duration {
+            return Ok(dutch_auction.floor_price);
+        }
+
+        let decay = (self.price.checked_sub(dutch_auction.floor_price)
+            .ok_or(ErrorCode::NumericalOverflow)? as u128)
+            .checked_mul(elapsed as u128)
+            .ok_or(ErrorCode::NumericalOverflow)?
+            .checked_div(dutch_auction.duration as u128)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        let decay = u64::try_from(decay).map_err(|_| ErrorCode::NumericalOverflow)?;
+
+        Ok(self.price.checked_sub(decay).ok_or(ErrorCode::NumericalOverflow)?)
+    }
+}
+
+/// `Listing` layout the program was first deployed with, derived from
+/// `[b"listing", seller, name]`. Shares the `Listing` discriminator and is
+/// only read by `migrate_listing` and clients.
+#[derive(AnchorDeserialize)]
+pub struct ListingV0 {
+    pub seller: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub name: String,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl ListingV0 {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // seller pubkey
+        8 +  // price
+        8 +  // quantity
+        4 + MAX_NAME_BYTES + // name (String of at most MAX_NAME_BYTES UTF-8 bytes)
+        1 +  // active
+        1;   // bump
+}
+
+/// Reads a legacy listing as version 0 of the current layout, with
+/// defaults for everything it did not record. It keeps its name-derived
+/// address, so `nonce` is meaningless until `migrate_listing` moves it.
+impl From<ListingV0> for Listing {
+    fn from(legacy: ListingV0) -> Self {
+        Listing {
+            version: 0,
+            seller: legacy.seller,
+            nonce: 0,
+            payment_kind: PaymentKind::Spl,
+            payment_mint: Pubkey::default(),
+            price: legacy.price,
+            quantity: legacy.quantity,
+            name: legacy.name,
+            metadata: ListingMetadata {
+                description: String::new(),
+                uri: String::new(),
+                category: Category::Other,
+                tags: vec![],
+            },
+            dutch_auction: None,
+            expires_at: None,
+            buyer_restriction: None,
+            max_per_buyer: None,
+            sale_count: 0,
+            units_sold: 0,
+            total_volume: 0,
+            active: legacy.active,
+            bump: legacy.bump,
+        }
+    }
+}
+
+/// `MarketplaceConfig` layout before the pause switch was added. Shares the
+/// `MarketplaceConfig` discriminator and is only read by `migrate_config`.
+#[derive(AnchorDeserialize)]
+pub struct MarketplaceConfigV0 {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+impl From<MarketplaceConfigV0> for MarketplaceConfig {
+    fn from(legacy: MarketplaceConfigV0) -> Self {
+        MarketplaceConfig {
+            admin: legacy.admin,
+            fee_bps: legacy.fee_bps,
+            bump: legacy.bump,
+            paused: false,
+        }
+    }
+}
+
+/// One listing to buy from in `purchase_batch`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchPurchase {
+    /// Index of the listing's first account in the remaining accounts.
+    pub listing_index: u8,
+    pub quantity: u64,
+    pub max_price: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentKind {
+    /// Paid in SPL tokens of `Listing::payment_mint`.
+    Spl,
+    /// Paid in lamports.
+    Native,
+    /// Paid in tokens of another item mint, `Listing::payment_mint`, sent
+    /// straight to the seller by `swap`.
+    Swap,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DutchAuction {
+    pub floor_price: u64,
+    pub start_time: i64,
+    pub duration: i64,
+}
+
+impl DutchAuction {
+    const LEN: usize = 8 + // floor price
+        8 +  // start time
+        8;   // duration
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ListingMetadata {
+    pub description: String,
+    /// Off-chain metadata, e.g. a JSON document with images.
+    pub uri: String,
+    pub category: Category,
+    pub tags: Vec<String>,
+}
+
+impl ListingMetadata {
+    const LEN: usize = 4 + MAX_DESCRIPTION_BYTES + // description
+        4 + MAX_URI_BYTES + // uri
+        1 +  // category
+        4 + MAX_TAGS * (4 + MAX_TAG_BYTES); // tags
+
+    /// Checks every field against its maximum UTF-8 byte length.
+    pub fn validate(&self) -> Result<()> {
+        require!(self.description.len() <= MAX_DESCRIPTION_BYTES, ErrorCode::DescriptionTooLong);
+        require!(self.uri.len() <= MAX_URI_BYTES, ErrorCode::UriTooLong);
+        require!(self.tags.len() <= MAX_TAGS, ErrorCode::TooManyTags);
+        for tag in &self.tags {
+            require!(!tag.is_empty(), ErrorCode::InvalidTag);
+            require!(tag.len() <= MAX_TAG_BYTES, ErrorCode::InvalidTag);
+        }
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Other,
+    Art,
+    Collectible,
+    Gaming,
+    Music,
+    Utility,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuyerRestriction {
+    /// Only `buyer` may purchase.
+    Reserved { buyer: Pubkey },
+    /// Only buyers whose key is a leaf of the Merkle tree with this root may
+    /// purchase. Leaves are `keccak(buyer)` and pairs are hashed in sorted order.
+    Allowlist { root: [u8; 32] },
+}
+
+impl BuyerRestriction {
+    const LEN: usize = 1 + // variant
+        32;  // buyer pubkey or Merkle root
+
+    /// Whether `buyer` may purchase, given a Merkle `proof` for allowlists.
+    pub fn allows(&self, buyer: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        match self {
+            BuyerRestriction::Reserved { buyer: reserved_buyer } => buyer == reserved_buyer,
+            BuyerRestriction::Allowlist { root } => {
+                let leaf = keccak::hashv(&[buyer.as_ref()]).to_bytes();
+                let computed = proof.iter().fold(leaf, |node, sibling| {
+                    if node <= *sibling {
+                        keccak::hashv(&[&node, sibling]).to_bytes()
+                    } else {
+                        keccak::hashv(&[sibling, &node]).to_bytes()
+                    }
+                });
+                computed == *root
+            }
+        }
+    }
+}
+
+#[account]
+pub struct SellerProfile {
+    pub seller: Pubkey,
+    /// Nonce the seller's next listing will be derived from.
+    pub listing_nonce: u64,
+    pub bump: u8,
+    /// Nonce the seller's next auction will be derived from.
+    pub auction_nonce: u64,
+}
+
+impl SellerProfile {
+    const LEN: usize = 8 + // discriminator
+        32 + // seller pubkey
+        8 +  // listing nonce
+        1 +  // bump
+        8;   // auction nonce
+}
+
+#[account]
+pub struct BuyerReceipt {
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub quantity: u64,
+    pub bump: u8,
+}
+
+impl BuyerReceipt {
+    const LEN: usize = 8 + // discriminator
+        32 + // listing pubkey
+        32 + // buyer pubkey
+        8 +  // cumulative quantity
+        1;   // bump
+}
+
+#[account]
+pub struct SaleReceipt {
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub quantity: u64,
+    pub unit_price: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub bump: u8,
+}
+
+impl SaleReceipt {
+    const LEN: usize = 8 + // discriminator
+        32 + // listing pubkey
+        32 + // buyer pubkey
+        8 +  // quantity
+        8 +  // unit price
+        8 +  // timestamp
+        8 +  // slot
+        1;   // bump
+}
+
+#[account]
+pub struct Offer {
+    pub buyer: Pubkey,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Offer {
+    const LEN: usize = 8 + // discriminator
+        32 + // buyer pubkey
+        32 + // item mint
+        32 + // payment mint
+        8 +  // price
+        8 +  // quantity
+        1 +  // active
+        1;   // bump
+}
+
+#[account]
+pub struct MarketplaceConfig {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+    /// Emergency stop; blocks new listings, offers, bids and sales while set.
+    pub paused: bool,
+}
+
+impl MarketplaceConfig {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // admin pubkey
+        2 +  // fee basis points
+        1 +  // bump
+        1;   // paused
+
+    /// Protocol fee owed on a sale of `amount` payment tokens.
+    pub fn fee_for(&self, amount: u64) -> Result<u64> {
+        bps_of(amount, self.fee_bps)
+    }
+}
+
+#[account]
+pub struct Auction {
+    pub seller: Pubkey,
+    /// Seller profile nonce this auction's address is derived from.
+    pub nonce: u64,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub name: String,
+    pub quantity: u64,
+    pub reserve_price: u64,
+    pub min_bid_increment: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub highest_bidder: Pubkey,
+    pub highest_bid: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Auction {
+    const LEN: usize = 8 + // discriminator
+        32 + // seller pubkey
+        8 +  // nonce
+        32 + // item mint
+        32 + // payment mint
+        4 + MAX_NAME_BYTES + // name (String of at most MAX_NAME_BYTES UTF-8 bytes)
+        8 +  // quantity
+        8 +  // reserve price
+        8 +  // min bid increment
+        8 +  // start time
+        8 +  // end time
+        32 + // highest bidder
+        8 +  // highest bid
+        1 +  // active
+        1;   // bump
+
+    pub fn has_bids(&self) -> bool {
+        self.highest_bid > 0
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub share_bps: u16,
+}
+
+#[account]
+pub struct RoyaltyConfig {
+    pub item_mint: Pubkey,
+    pub authority: Pubkey,
+    pub creators: Vec<Creator>,
+    pub bump: u8,
+}
+
+impl RoyaltyConfig {
+    const LEN: usize = 8 + // discriminator
+        32 + // item mint
+        32 + // authority pubkey
+        4 + MAX_CREATORS * (32 + 2) + // creators (Vec with max length of MAX_CREATORS)
+        1;   // bump
+}
+
+#[event]
+pub struct ListingCreated {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub nonce: u64,
+    pub item_mint: Pubkey,
+    pub payment_kind: PaymentKind,
+    pub payment_mint: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+#[event]
+pub struct SellerProfileCreated {
+    pub seller_profile: Pubkey,
+    pub seller: Pubkey,
+}
+
+#[event]
+pub struct ListingPurchased {
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub quantity: u64,
+    pub unit_price: u64,
+    pub total_price: u64,
+    pub fee: u64,
+    pub royalties: u64,
+    pub remaining_quantity: u64,
+}
+
+#[event]
+pub struct ListingUpdated {
+    pub listing: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub active: bool,
+}
+
+#[event]
+pub struct ListingCancelled {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub quantity_returned: u64,
+}
+
+#[event]
+pub struct ListingExpired {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub cranker: Pubkey,
+    pub quantity_returned: u64,
+}
+
+#[event]
+pub struct ListingMigrated {
+    pub legacy_listing: Pubkey,
+    pub listing: Pubkey,
+    pub version: u8,
+}
+
+#[event]
+pub struct ListingClosed {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+}
+
+#[event]
+pub struct OfferCreated {
+    pub offer: Pubkey,
+    pub buyer: Pubkey,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+#[event]
+pub struct OfferAccepted {
+    pub offer: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub quantity: u64,
+    pub total_price: u64,
+    pub fee: u64,
+    pub royalties: u64,
+    pub remaining_quantity: u64,
+}
+
+#[event]
+pub struct OfferCancelled {
+    pub offer: Pubkey,
+    pub buyer: Pubkey,
+    pub refunded: u64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub paused: bool,
+}
+
+#[event]
+pub struct FeeVaultInitialized {
+    pub fee_vault: Pubkey,
+    pub payment_mint: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct FeesWithdrawn {
+    pub admin: Pubkey,
+    /// `Pubkey::default()` for lamport fees.
+    pub payment_mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RoyaltyConfigUpdated {
+    pub royalty_config: Pubkey,
+    pub item_mint: Pubkey,
+    pub creators: Vec<Creator>,
+}
+
+#[event]
+pub struct AuctionCreated {
+    pub auction: Pubkey,
+    pub seller: Pubkey,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub reserve_price: u64,
+    pub min_bid_increment: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub quantity: u64,
+}
+
+#[event]
+pub struct BidPlaced {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    /// `Pubkey::default()` for the first bid.
+    pub previous_bidder: Pubkey,
+}
+
+#[event]
+pub struct AuctionSettled {
+    pub auction: Pubkey,
+    pub seller: Pubkey,
+    pub winner: Pubkey,
+    pub winning_bid: u64,
+    pub fee: u64,
+    pub royalties: u64,
+}
+
+#[event]
+pub struct AuctionCancelled {
+    pub auction: Pubkey,
+    pub seller: Pubkey,
+}
+
+/// Codes are `ERROR_CODE_OFFSET` plus the declaration index, so new variants
+/// are only ever appended. `FromPrimitive` maps an index back to its variant.
+#[error_code]
+#[derive(FromPrimitive)]
+pub enum ErrorCode {
+    #[msg("Invalid price, must be greater than zero")]
+    InvalidPrice,
+    
+    #[msg("Invalid quantity, must be greater than zero")]
+    InvalidQuantity,
+
+    #[msg("Invalid name, must not be empty")]
+    InvalidName,
+    
+    #[msg("Name is too long, must be 32 bytes or less")]
+    NameTooLong,
 
     #[msg("Listing is not active")]
     ListingNotActive,
@@ -301,4 +3941,148 @@ pub enum ErrorCode {
 
     #[msg("Bump seed not in hash map")]
     BumpSeedNotInHashMap,
+
+    #[msg("Offer is not active")]
+    OfferNotActive,
+
+    #[msg("Invalid buyer")]
+    InvalidBuyer,
+
+    #[msg("Token account mint does not match")]
+    InvalidMint,
+
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+
+    #[msg("Fee basis points exceed the maximum allowed")]
+    InvalidFeeBps,
+
+    #[msg("Too many creators on royalty config")]
+    TooManyCreators,
+
+    #[msg("Invalid royalty basis points")]
+    InvalidRoyaltyBps,
+
+    #[msg("Invalid royalty config authority")]
+    InvalidRoyaltyAuthority,
+
+    #[msg("Invalid royalty config account")]
+    InvalidRoyaltyConfig,
+
+    #[msg("Missing or mismatched creator payment account")]
+    MissingCreatorAccount,
+
+    #[msg("Auction end time must be after its start time and in the future")]
+    InvalidAuctionTimes,
+
+    #[msg("Auction is not active")]
+    AuctionNotActive,
+
+    #[msg("Auction has not started yet")]
+    AuctionNotStarted,
+
+    #[msg("Auction has already ended")]
+    AuctionEnded,
+
+    #[msg("Auction has not ended yet")]
+    AuctionNotEnded,
+
+    #[msg("Bid is below the reserve price or minimum increment")]
+    BidTooLow,
+
+    #[msg("Auction already has bids")]
+    AuctionHasBids,
+
+    #[msg("Auction has no bids")]
+    NoBids,
+
+    #[msg("Missing or mismatched previous bidder payment account")]
+    MissingPreviousBidderAccount,
+
+    #[msg("Dutch auction floor must be below the start price and duration positive")]
+    InvalidDutchAuction,
+
+    #[msg("Price exceeds what the buyer agreed to pay")]
+    PriceMismatch,
+
+    #[msg("Listing is still active")]
+    ListingStillActive,
+
+    #[msg("Buyer payment account mint does not match the listing's payment mint")]
+    InvalidBuyerPaymentMint,
+
+    #[msg("Seller payment account mint does not match the listing's payment mint")]
+    InvalidSellerPaymentMint,
+
+    #[msg("Seller payment account is not owned by the listing's seller")]
+    InvalidSellerPaymentOwner,
+
+    #[msg("Payment account required for this listing's payment kind is missing")]
+    MissingPaymentAccount,
+
+    #[msg("Listing expiry must be in the future")]
+    InvalidExpiry,
+
+    #[msg("Listing has expired")]
+    ListingExpired,
+
+    #[msg("Listing has not expired")]
+    ListingNotExpired,
+
+    #[msg("Buyer is not allowed to purchase this listing")]
+    BuyerNotAllowed,
+
+    #[msg("Purchase exceeds the listing's per-buyer limit")]
+    BuyerLimitExceeded,
+
+    #[msg("Buyer receipt is required for listings with a per-buyer limit")]
+    MissingBuyerReceipt,
+
+    #[msg("Description is too long, must be 256 bytes or less")]
+    DescriptionTooLong,
+
+    #[msg("Metadata URI is too long, must be 200 bytes or less")]
+    UriTooLong,
+
+    #[msg("Too many tags, at most 5 are allowed")]
+    TooManyTags,
+
+    #[msg("Tags must be between 1 and 16 bytes")]
+    InvalidTag,
+
+    #[msg("Listing is already at the current version")]
+    ListingUpToDate,
+
+    #[msg("Marketplace is paused")]
+    MarketplacePaused,
+
+    #[msg("Listing index is outside the remaining accounts")]
+    InvalidBatchIndex,
+
+    #[msg("Listing appears more than once in the batch")]
+    DuplicateListing,
+
+    #[msg("Swap listings must be bought with swap")]
+    SwapListing,
+
+    #[msg("Listing is not a swap listing")]
+    NotASwapListing,
+
+    #[msg("Swap listings must be priced in a different mint than the item")]
+    InvalidSwapMint,
+
+    #[msg("Fee vault has not been initialized for the payment mint")]
+    MissingFeeVault,
+
+    #[msg("Minimum bid increment must be greater than zero")]
+    InvalidBidIncrement,
+
+    #[msg("Config is already at the current version")]
+    ConfigUpToDate,
+
+    #[msg("Token program does not own the mint")]
+    TokenProgramMismatch,
+
+    #[msg("Listing uses an older layout and must be migrated with migrate_listing")]
+    ListingNeedsMigration,
 }
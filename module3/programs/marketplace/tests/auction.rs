@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use marketplace::{Auction, Creator};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+#[tokio::test]
+async fn settles_an_auction_to_the_highest_bidder() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    h.place_bid(auction, RESERVE_PRICE, None).await.unwrap();
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - RESERVE_PRICE);
+
+    // A rival outbids the buyer, whose bid is refunded in the same instruction
+    let rival = Keypair::new();
+    let payment_mint = h.payment_mint;
+    let rival_payment_account = h.create_token_account(&payment_mint, &rival.pubkey()).await;
+    h.mint_to(&payment_mint, &rival_payment_account, STARTING_TOKENS).await;
+    let winning_bid = RESERVE_PRICE * 2;
+    let buyer_payment_account = h.buyer_payment_account;
+    h.place_bid_from(&rival, rival_payment_account, auction, winning_bid, Some(buyer_payment_account))
+        .await
+        .unwrap();
+
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS);
+    assert_eq!(h.token_balance(bid_escrow_pda(&auction)).await, winning_bid);
+    let state: Auction = h.account(auction).await;
+    assert_eq!(state.highest_bidder, rival.pubkey());
+    assert_eq!(state.highest_bid, winning_bid);
+
+    let item_mint = h.item_mint;
+    let rival_item_account = h.create_token_account(&item_mint, &rival.pubkey()).await;
+    h.set_time(state.end_time).await;
+    let seller_before = h.lamports(h.seller.pubkey()).await;
+    let rent = h.lamports(auction).await
+        + h.lamports(escrow_pda(&auction)).await
+        + h.lamports(bid_escrow_pda(&auction)).await;
+    h.settle_auction(auction, rival_item_account).await.unwrap();
+
+    // The winner gets the item, the seller the bid less the fee, and the
+    // auction and both escrows are closed to the seller
+    let fee = winning_bid * FEE_BPS as u64 / 10_000;
+    assert_eq!(h.token_balance(rival_item_account).await, 1);
+    assert_eq!(h.token_balance(rival_payment_account).await, STARTING_TOKENS - winning_bid);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, winning_bid - fee);
+    assert_eq!(h.token_balance(fee_vault_pda(&h.payment_mint)).await, fee);
+    assert!(!h.account_exists(auction).await);
+    assert!(!h.account_exists(escrow_pda(&auction)).await);
+    assert!(!h.account_exists(bid_escrow_pda(&auction)).await);
+    assert_eq!(h.lamports(h.seller.pubkey()).await, seller_before + rent);
+}
+
+#[tokio::test]
+async fn pays_royalties_when_settling_an_auction() {
+    let mut h = Harness::new().await;
+    let creator = Keypair::new().pubkey();
+    h.create_royalty_config(None, vec![Creator { address: creator, share_bps: 1_000 }])
+        .await
+        .unwrap();
+    let payment_mint = h.payment_mint;
+    let creator_account = h.create_token_account(&payment_mint, &creator).await;
+    let auction = h.create_open_auction().await;
+    let bid = RESERVE_PRICE * 2;
+    h.place_bid(auction, bid, None).await.unwrap();
+
+    let state: Auction = h.account(auction).await;
+    h.set_time(state.end_time).await;
+    let winner_token_account = h.buyer_item_account;
+    h.settle_auction_with_creators(auction, winner_token_account, &[creator_account])
+        .await
+        .unwrap();
+
+    let fee = bid * FEE_BPS as u64 / 10_000;
+    let royalties = bid / 10;
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 1);
+    assert_eq!(h.token_balance(creator_account).await, royalties);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, bid - fee - royalties);
+}
+
+#[tokio::test]
+async fn cancels_an_auction_without_bids() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS - 1);
+    let seller_before = h.lamports(h.seller.pubkey()).await;
+    let rent = h.lamports(auction).await
+        + h.lamports(escrow_pda(&auction)).await
+        + h.lamports(bid_escrow_pda(&auction)).await;
+
+    h.cancel_auction(auction).await.unwrap();
+
+    // The item goes back and the seller recovers the rent of all three accounts
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS);
+    assert!(!h.account_exists(auction).await);
+    assert!(!h.account_exists(escrow_pda(&auction)).await);
+    assert!(!h.account_exists(bid_escrow_pda(&auction)).await);
+    assert_eq!(h.lamports(h.seller.pubkey()).await, seller_before + rent);
+}
+
+#[tokio::test]
+async fn auctions_again_under_the_next_nonce() {
+    let mut h = Harness::new().await;
+    let first = h.create_open_auction().await;
+    h.cancel_auction(first).await.unwrap();
+
+    let second = h.create_open_auction().await;
+
+    assert_eq!(first, auction_pda(&h.seller.pubkey(), 0));
+    assert_eq!(second, auction_pda(&h.seller.pubkey(), 1));
+    assert_eq!(h.next_auction_nonce(&h.seller.pubkey()).await, 2);
+}
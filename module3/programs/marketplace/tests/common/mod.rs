@@ -0,0 +1,939 @@
+//! In-process fixtures shared by the marketplace integration tests.
+//!
+//! The program runs natively inside `solana-program-test`, alongside the SPL
+//! Token programs bundled with it, so these tests need neither a validator
+//! nor a BPF toolchain.
+
+#![allow(dead_code)]
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::{
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee, ExtensionType},
+    },
+};
+use marketplace::{accounts, instruction, BatchPurchase, Category, Creator, ErrorCode, ListingMetadata};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+pub const FEE_BPS: u16 = 250;
+pub const ITEM_PRICE: u64 = 100;
+pub const ITEM_QUANTITY: u64 = 5;
+pub const ITEM_NAME: &str = "Test Item";
+pub const AUCTION_NAME: &str = "Test Auction";
+pub const RESERVE_PRICE: u64 = 50;
+
+/// Items and payment tokens each user starts with.
+pub const STARTING_TOKENS: u64 = 1_000_000;
+
+/// A started test validator with an initialized config and fee vault, plus a
+/// seller and buyer holding item and payment tokens. The context payer is the
+/// mint authority of both mints.
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub seller: Keypair,
+    pub buyer: Keypair,
+    pub item_mint: Pubkey,
+    pub payment_mint: Pubkey,
+    pub seller_item_account: Pubkey,
+    pub buyer_item_account: Pubkey,
+    pub seller_payment_account: Pubkey,
+    pub buyer_payment_account: Pubkey,
+}
+
+impl Harness {
+    pub async fn new() -> Self {
+        let mut program_test = ProgramTest::new(
+            "marketplace",
+            marketplace::ID,
+            processor!(marketplace::entry),
+        );
+        // Always run the program natively, even if a stale .so is lying around
+        program_test.prefer_bpf(false);
+        let mut context = program_test.start_with_context().await;
+
+        let admin = Keypair::new();
+        let seller = Keypair::new();
+        let buyer = Keypair::new();
+        for user in [&admin, &seller, &buyer] {
+            let ix = system_instruction::transfer(
+                &context.payer.pubkey(),
+                &user.pubkey(),
+                10 * LAMPORTS_PER_SOL,
+            );
+            process(&mut context, &[ix], &[]).await.unwrap();
+        }
+
+        let item_mint = create_mint(&mut context).await;
+        let payment_mint = create_mint(&mut context).await;
+
+        let seller_item_account = create_token_account(&mut context, &item_mint, &seller.pubkey()).await;
+        let buyer_item_account = create_token_account(&mut context, &item_mint, &buyer.pubkey()).await;
+        let seller_payment_account = create_token_account(&mut context, &payment_mint, &seller.pubkey()).await;
+        let buyer_payment_account = create_token_account(&mut context, &payment_mint, &buyer.pubkey()).await;
+        mint_to(&mut context, &item_mint, &seller_item_account, STARTING_TOKENS).await;
+        mint_to(&mut context, &payment_mint, &buyer_payment_account, STARTING_TOKENS).await;
+
+        let initialize_config = build(
+            accounts::InitializeConfig {
+                admin: admin.pubkey(),
+                config: config_pda(),
+                system_program: system_program::ID,
+            },
+            instruction::InitializeConfig { fee_bps: FEE_BPS },
+        );
+        let initialize_fee_vault = build(
+            accounts::InitializeFeeVault {
+                admin: admin.pubkey(),
+                config: config_pda(),
+                fee_vault: fee_vault_pda(&payment_mint),
+                payment_mint,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            },
+            instruction::InitializeFeeVault {},
+        );
+        process(&mut context, &[initialize_config, initialize_fee_vault], &[&admin])
+            .await
+            .unwrap();
+
+        Self {
+            context,
+            admin,
+            seller,
+            buyer,
+            item_mint,
+            payment_mint,
+            seller_item_account,
+            buyer_item_account,
+            seller_payment_account,
+            buyer_payment_account,
+        }
+    }
+
+    /// Fetches and deserializes a program account.
+    pub async fn account<T: AccountDeserialize>(&mut self, address: Pubkey) -> T {
+        let account = self.context.banks_client.get_account(address).await.unwrap()
+            .expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn account_exists(&mut self, address: Pubkey) -> bool {
+        self.context.banks_client.get_account(address).await.unwrap().is_some()
+    }
+
+    pub async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.context.banks_client.get_balance(address).await.unwrap()
+    }
+
+    pub async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(address).await.unwrap()
+            .expect("token account not found");
+        // Token-2022 accounts start with the same layout, followed by extensions
+        spl_token::state::Account::unpack_from_slice(&account.data[..spl_token::state::Account::LEN])
+            .unwrap()
+            .amount
+    }
+
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        create_token_account(&mut self.context, mint, owner).await
+    }
+
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        mint_to(&mut self.context, mint, account, amount).await
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        self.clock().await.unix_timestamp
+    }
+
+    /// Moves the cluster clock to `unix_timestamp` without advancing the slot.
+    pub async fn set_time(&mut self, unix_timestamp: i64) {
+        let mut clock = self.clock().await;
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn clock(&mut self) -> Clock {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    /// Waits for a new blockhash so an identical transaction is not rejected
+    /// as already processed.
+    pub async fn refresh_blockhash(&mut self) {
+        self.context.get_new_latest_blockhash().await.unwrap();
+    }
+
+    /// Replaces the item mint with a Token-2022 mint that withholds
+    /// `transfer_fee_bps` of every transfer, giving the seller and buyer new
+    /// item accounts and the seller `STARTING_TOKENS` items.
+    pub async fn use_token_2022_items(&mut self, transfer_fee_bps: u16) {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = ExtensionType::get_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ]);
+        let instructions = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &spl_token_2022::ID,
+            ),
+            transfer_fee::instruction::initialize_transfer_fee_config(
+                &spl_token_2022::ID,
+                &mint.pubkey(),
+                None,
+                None,
+                transfer_fee_bps,
+                u64::MAX,
+            )
+            .unwrap(),
+            spl_token_2022::instruction::initialize_mint2(&spl_token_2022::ID, &mint.pubkey(), &payer, None, 0)
+                .unwrap(),
+        ];
+        process(&mut self.context, &instructions, &[&mint]).await.unwrap();
+        self.item_mint = mint.pubkey();
+
+        let space = ExtensionType::get_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::TransferFeeAmount,
+        ]);
+        let mut accounts = vec![];
+        for owner in [self.seller.pubkey(), self.buyer.pubkey()] {
+            let account = Keypair::new();
+            let instructions = [
+                system_instruction::create_account(
+                    &payer,
+                    &account.pubkey(),
+                    rent.minimum_balance(space),
+                    space as u64,
+                    &spl_token_2022::ID,
+                ),
+                spl_token_2022::instruction::initialize_account3(
+                    &spl_token_2022::ID,
+                    &account.pubkey(),
+                    &self.item_mint,
+                    &owner,
+                )
+                .unwrap(),
+            ];
+            process(&mut self.context, &instructions, &[&account]).await.unwrap();
+            accounts.push(account.pubkey());
+        }
+        self.seller_item_account = accounts[0];
+        self.buyer_item_account = accounts[1];
+
+        let ix = spl_token_2022::instruction::mint_to(
+            &spl_token_2022::ID,
+            &self.item_mint,
+            &self.seller_item_account,
+            &payer,
+            &[],
+            STARTING_TOKENS,
+        )
+        .unwrap();
+        process(&mut self.context, &[ix], &[]).await.unwrap();
+    }
+
+    /// Accounts for the seller's next listing, paid for in the payment mint.
+    pub async fn create_listing_accounts(&mut self) -> accounts::CreateListing {
+        let seller = self.seller.pubkey();
+        let listing = listing_pda(&seller, self.next_listing_nonce(&seller).await);
+        accounts::CreateListing {
+            seller,
+            seller_profile: seller_profile_pda(&seller),
+            config: config_pda(),
+            listing,
+            seller_token_account: self.seller_item_account,
+            escrow_token_account: escrow_pda(&listing),
+            item_mint: self.item_mint,
+            payment_mint: Some(self.payment_mint),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+    }
+
+    /// Accounts for the seller moving `legacy_listing` to their next
+    /// listing nonce, priced in the payment mint.
+    pub async fn migrate_listing_accounts(&mut self, legacy_listing: Pubkey) -> accounts::MigrateListing {
+        let seller = self.seller.pubkey();
+        let listing = listing_pda(&seller, self.next_listing_nonce(&seller).await);
+        accounts::MigrateListing {
+            seller,
+            seller_profile: seller_profile_pda(&seller),
+            legacy_listing,
+            legacy_escrow_account: escrow_pda(&legacy_listing),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            item_mint: self.item_mint,
+            payment_mint: Some(self.payment_mint),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+    }
+
+    pub async fn next_listing_nonce(&mut self, seller: &Pubkey) -> u64 {
+        let address = seller_profile_pda(seller);
+        if !self.account_exists(address).await {
+            return 0;
+        }
+        self.account::<marketplace::SellerProfile>(address).await.listing_nonce
+    }
+
+    pub async fn next_auction_nonce(&mut self, seller: &Pubkey) -> u64 {
+        let address = seller_profile_pda(seller);
+        if !self.account_exists(address).await {
+            return 0;
+        }
+        self.account::<marketplace::SellerProfile>(address).await.auction_nonce
+    }
+
+    /// Lists `args` from the seller, returning the new listing's address.
+    pub async fn create_listing_with(
+        &mut self,
+        accounts: accounts::CreateListing,
+        args: instruction::CreateListing,
+    ) -> Result<Pubkey, BanksClientError> {
+        let listing = accounts.listing;
+        let ix = build(accounts, args);
+        process(&mut self.context, &[ix], &[&self.seller]).await?;
+        Ok(listing)
+    }
+
+    /// Lists `ITEM_QUANTITY` items at `ITEM_PRICE` payment tokens each.
+    pub async fn create_listing(&mut self) -> Pubkey {
+        let accounts = self.create_listing_accounts().await;
+        self.create_listing_with(accounts, listing_args(ITEM_PRICE, ITEM_QUANTITY))
+            .await
+            .unwrap()
+    }
+
+    /// Accounts for the buyer purchasing from an SPL-priced `listing`,
+    /// without buyer or sale receipts.
+    pub fn purchase_accounts(&self, listing: Pubkey) -> accounts::Purchase {
+        accounts::Purchase {
+            buyer: self.buyer.pubkey(),
+            listing,
+            seller: self.seller.pubkey(),
+            escrow_token_account: escrow_pda(&listing),
+            buyer_token_account: self.buyer_item_account,
+            buyer_payment_account: Some(self.buyer_payment_account),
+            seller_payment_account: Some(self.seller_payment_account),
+            item_mint: self.item_mint,
+            payment_mint: Some(self.payment_mint),
+            config: config_pda(),
+            fee_vault: Some(fee_vault_pda(&self.payment_mint)),
+            royalty_config: royalty_config_pda(&self.item_mint),
+            buyer_receipt: None,
+            sale_receipt: None,
+            token_program: spl_token::ID,
+            payment_token_program: Some(spl_token::ID),
+            system_program: system_program::ID,
+        }
+    }
+
+    pub async fn purchase_with(
+        &mut self,
+        accounts: accounts::Purchase,
+        args: instruction::Purchase,
+    ) -> Result<(), BanksClientError> {
+        let ix = build(accounts, args);
+        process(&mut self.context, &[ix], &[&self.buyer]).await
+    }
+
+    /// Buys `quantity` items from `listing` at `ITEM_PRICE`.
+    pub async fn purchase(&mut self, listing: Pubkey, quantity: u64) -> Result<(), BanksClientError> {
+        let accounts = self.purchase_accounts(listing);
+        self.purchase_with(accounts, purchase_args(quantity, ITEM_PRICE)).await
+    }
+
+    /// Lists `ITEM_QUANTITY` items as a swap listing priced at `ITEM_PRICE`
+    /// payment tokens each.
+    pub async fn create_swap_listing(&mut self) -> Pubkey {
+        let accounts = self.create_listing_accounts().await;
+        self.create_swap_listing_with(accounts, swap_listing_args(ITEM_PRICE, ITEM_QUANTITY))
+            .await
+            .unwrap()
+    }
+
+    pub async fn create_swap_listing_with(
+        &mut self,
+        accounts: accounts::CreateListing,
+        args: instruction::CreateSwapListing,
+    ) -> Result<Pubkey, BanksClientError> {
+        let listing = accounts.listing;
+        let ix = build(accounts, args);
+        process(&mut self.context, &[ix], &[&self.seller]).await?;
+        Ok(listing)
+    }
+
+    /// Accounts for the buyer swapping payment tokens for items from
+    /// `listing`, without buyer or sale receipts.
+    pub fn swap_accounts(&self, listing: Pubkey) -> accounts::Swap {
+        accounts::Swap {
+            buyer: self.buyer.pubkey(),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            buyer_token_account: self.buyer_item_account,
+            buyer_payment_account: self.buyer_payment_account,
+            seller_payment_account: self.seller_payment_account,
+            item_mint: self.item_mint,
+            payment_mint: self.payment_mint,
+            config: config_pda(),
+            buyer_receipt: None,
+            sale_receipt: None,
+            token_program: spl_token::ID,
+            payment_token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Swaps for `quantity` items from `listing` at `ITEM_PRICE`.
+    pub async fn swap(&mut self, listing: Pubkey, quantity: u64) -> Result<(), BanksClientError> {
+        let ix = build(
+            self.swap_accounts(listing),
+            instruction::Swap {
+                quantity,
+                max_price: ITEM_PRICE,
+                proof: vec![],
+            },
+        );
+        process(&mut self.context, &[ix], &[&self.buyer]).await
+    }
+
+    /// The buyer's `purchase_batch` accounts for an SPL-priced `listing`,
+    /// without creator accounts.
+    pub fn purchase_batch_group(&self, listing: Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(listing, false),
+            AccountMeta::new(self.seller.pubkey(), false),
+            AccountMeta::new(escrow_pda(&listing), false),
+            AccountMeta::new(self.buyer_item_account, false),
+            AccountMeta::new_readonly(self.item_mint, false),
+            AccountMeta::new_readonly(royalty_config_pda(&self.item_mint), false),
+            AccountMeta::new(self.buyer_payment_account, false),
+            AccountMeta::new(self.seller_payment_account, false),
+            AccountMeta::new_readonly(self.payment_mint, false),
+            AccountMeta::new(fee_vault_pda(&self.payment_mint), false),
+            AccountMeta::new_readonly(marketplace::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ]
+    }
+
+    /// Buys `(group, quantity, max_price)` in one `purchase_batch`, laying
+    /// the groups out back to back.
+    pub async fn purchase_batch(
+        &mut self,
+        purchases: &[(Vec<AccountMeta>, u64, u64)],
+    ) -> Result<(), BanksClientError> {
+        let mut items = vec![];
+        let mut remaining = vec![];
+        for (group, quantity, max_price) in purchases {
+            items.push(BatchPurchase {
+                listing_index: remaining.len() as u8,
+                quantity: *quantity,
+                max_price: *max_price,
+            });
+            remaining.extend_from_slice(group);
+        }
+
+        let mut ix = build(self.purchase_batch_accounts(), instruction::PurchaseBatch { items });
+        ix.accounts.extend(remaining);
+        process(&mut self.context, &[ix], &[&self.buyer]).await
+    }
+
+    pub fn purchase_batch_accounts(&self) -> accounts::PurchaseBatch {
+        accounts::PurchaseBatch {
+            buyer: self.buyer.pubkey(),
+            config: config_pda(),
+            system_program: system_program::ID,
+        }
+    }
+
+    pub fn cancel_listing_accounts(&self, listing: Pubkey) -> accounts::CancelListing {
+        accounts::CancelListing {
+            seller: self.seller.pubkey(),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            seller_token_account: self.seller_item_account,
+            item_mint: self.item_mint,
+            token_program: spl_token::ID,
+        }
+    }
+
+    pub async fn cancel_listing(&mut self, listing: Pubkey) -> Result<(), BanksClientError> {
+        let ix = build(self.cancel_listing_accounts(listing), instruction::CancelListing {});
+        process(&mut self.context, &[ix], &[&self.seller]).await
+    }
+
+    /// The buyer offers `price` per item for `quantity` items.
+    pub async fn create_offer(&mut self, price: u64, quantity: u64) -> Result<Pubkey, BanksClientError> {
+        let offer = offer_pda(&self.buyer.pubkey(), &self.item_mint);
+        let ix = build(
+            accounts::CreateOffer {
+                buyer: self.buyer.pubkey(),
+                offer,
+                buyer_payment_account: self.buyer_payment_account,
+                escrow_payment_account: offer_escrow_pda(&offer),
+                item_mint: self.item_mint,
+                payment_mint: self.payment_mint,
+                config: config_pda(),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            },
+            instruction::CreateOffer { price, quantity },
+        );
+        process(&mut self.context, &[ix], &[&self.buyer]).await?;
+        Ok(offer)
+    }
+
+    /// Accounts for the seller filling the buyer's `offer`.
+    pub fn accept_offer_accounts(&self, offer: Pubkey) -> accounts::AcceptOffer {
+        accounts::AcceptOffer {
+            seller: self.seller.pubkey(),
+            buyer: self.buyer.pubkey(),
+            offer,
+            escrow_payment_account: offer_escrow_pda(&offer),
+            seller_token_account: self.seller_item_account,
+            buyer_token_account: self.buyer_item_account,
+            seller_payment_account: self.seller_payment_account,
+            buyer_payment_account: self.buyer_payment_account,
+            config: config_pda(),
+            fee_vault: fee_vault_pda(&self.payment_mint),
+            royalty_config: royalty_config_pda(&self.item_mint),
+            item_mint: self.item_mint,
+            payment_mint: self.payment_mint,
+            token_program: spl_token::ID,
+            payment_token_program: spl_token::ID,
+        }
+    }
+
+    /// Sells `quantity` items into the buyer's `offer`.
+    pub async fn accept_offer(&mut self, offer: Pubkey, quantity: u64) -> Result<(), BanksClientError> {
+        let ix = build(self.accept_offer_accounts(offer), instruction::AcceptOffer { quantity });
+        process(&mut self.context, &[ix], &[&self.seller]).await
+    }
+
+    pub fn cancel_offer_accounts(&self, offer: Pubkey) -> accounts::CancelOffer {
+        accounts::CancelOffer {
+            buyer: self.buyer.pubkey(),
+            offer,
+            escrow_payment_account: offer_escrow_pda(&offer),
+            buyer_payment_account: self.buyer_payment_account,
+            payment_mint: self.payment_mint,
+            token_program: spl_token::ID,
+        }
+    }
+
+    pub async fn cancel_offer(&mut self, offer: Pubkey) -> Result<(), BanksClientError> {
+        let ix = build(self.cancel_offer_accounts(offer), instruction::CancelOffer {});
+        process(&mut self.context, &[ix], &[&self.buyer]).await
+    }
+
+    /// Registers royalty `creators` for the item mint, signed by `authority`
+    /// or by the context payer (the mint authority) when `None`.
+    pub async fn create_royalty_config(
+        &mut self,
+        authority: Option<&Keypair>,
+        creators: Vec<Creator>,
+    ) -> Result<(), BanksClientError> {
+        let ix = build(
+            accounts::CreateRoyaltyConfig {
+                authority: authority.map_or(self.context.payer.pubkey(), |authority| authority.pubkey()),
+                royalty_config: royalty_config_pda(&self.item_mint),
+                item_mint: self.item_mint,
+                system_program: system_program::ID,
+            },
+            instruction::CreateRoyaltyConfig { creators },
+        );
+        let signers: Vec<&Keypair> = authority.into_iter().collect();
+        process(&mut self.context, &[ix], &signers).await
+    }
+
+    /// Auctions one item from the seller between `start_time` and `end_time`.
+    pub async fn create_auction(&mut self, start_time: i64, end_time: i64) -> Result<Pubkey, BanksClientError> {
+        self.create_auction_with(10, start_time, end_time).await
+    }
+
+    pub async fn create_auction_with(
+        &mut self,
+        min_bid_increment: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<Pubkey, BanksClientError> {
+        self.create_auction_from(instruction::CreateAuction {
+            reserve_price: RESERVE_PRICE,
+            min_bid_increment,
+            start_time,
+            end_time,
+            quantity: 1,
+            name: AUCTION_NAME.to_string(),
+        })
+        .await
+    }
+
+    pub async fn create_auction_from(&mut self, args: instruction::CreateAuction) -> Result<Pubkey, BanksClientError> {
+        let seller = self.seller.pubkey();
+        let auction = auction_pda(&seller, self.next_auction_nonce(&seller).await);
+        let ix = build(
+            accounts::CreateAuction {
+                seller,
+                seller_profile: seller_profile_pda(&seller),
+                auction,
+                seller_token_account: self.seller_item_account,
+                escrow_token_account: escrow_pda(&auction),
+                bid_escrow_account: bid_escrow_pda(&auction),
+                item_mint: self.item_mint,
+                payment_mint: self.payment_mint,
+                config: config_pda(),
+                token_program: spl_token::ID,
+                payment_token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            },
+            args,
+        );
+        process(&mut self.context, &[ix], &[&self.seller]).await?;
+        Ok(auction)
+    }
+
+    /// Auctions one item that opened a minute ago and closes in an hour.
+    pub async fn create_open_auction(&mut self) -> Pubkey {
+        let now = self.now().await;
+        self.create_auction(now - 60, now + 3_600).await.unwrap()
+    }
+
+    /// Bids `amount` from the buyer, refunding the outbid bidder's
+    /// `previous_bidder_payment_account`.
+    pub async fn place_bid(
+        &mut self,
+        auction: Pubkey,
+        amount: u64,
+        previous_bidder_payment_account: Option<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let buyer = self.buyer.insecure_clone();
+        let buyer_payment_account = self.buyer_payment_account;
+        self.place_bid_from(&buyer, buyer_payment_account, auction, amount, previous_bidder_payment_account)
+            .await
+    }
+
+    pub async fn place_bid_from(
+        &mut self,
+        bidder: &Keypair,
+        bidder_payment_account: Pubkey,
+        auction: Pubkey,
+        amount: u64,
+        previous_bidder_payment_account: Option<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let ix = build(
+            accounts::PlaceBid {
+                bidder: bidder.pubkey(),
+                auction,
+                bid_escrow_account: bid_escrow_pda(&auction),
+                bidder_payment_account,
+                previous_bidder_payment_account,
+                payment_mint: self.payment_mint,
+                config: config_pda(),
+                token_program: spl_token::ID,
+            },
+            instruction::PlaceBid { amount },
+        );
+        process(&mut self.context, &[ix], &[bidder]).await
+    }
+
+    pub async fn settle_auction(&mut self, auction: Pubkey, winner_token_account: Pubkey) -> Result<(), BanksClientError> {
+        self.settle_auction_with_creators(auction, winner_token_account, &[]).await
+    }
+
+    /// Settles `auction`, paying royalties into `creator_accounts`.
+    pub async fn settle_auction_with_creators(
+        &mut self,
+        auction: Pubkey,
+        winner_token_account: Pubkey,
+        creator_accounts: &[Pubkey],
+    ) -> Result<(), BanksClientError> {
+        let ix = build_with_remaining(
+            accounts::SettleAuction {
+                seller: self.seller.pubkey(),
+                auction,
+                escrow_token_account: escrow_pda(&auction),
+                bid_escrow_account: bid_escrow_pda(&auction),
+                winner_token_account,
+                seller_payment_account: self.seller_payment_account,
+                config: config_pda(),
+                fee_vault: fee_vault_pda(&self.payment_mint),
+                royalty_config: royalty_config_pda(&self.item_mint),
+                item_mint: self.item_mint,
+                payment_mint: self.payment_mint,
+                token_program: spl_token::ID,
+                payment_token_program: spl_token::ID,
+            },
+            instruction::SettleAuction {},
+            creator_accounts,
+        );
+        process(&mut self.context, &[ix], &[]).await
+    }
+
+    pub async fn cancel_auction(&mut self, auction: Pubkey) -> Result<(), BanksClientError> {
+        let ix = build(
+            accounts::CancelAuction {
+                seller: self.seller.pubkey(),
+                auction,
+                escrow_token_account: escrow_pda(&auction),
+                bid_escrow_account: bid_escrow_pda(&auction),
+                seller_token_account: self.seller_item_account,
+                seller_payment_account: self.seller_payment_account,
+                item_mint: self.item_mint,
+                payment_mint: self.payment_mint,
+                token_program: spl_token::ID,
+                payment_token_program: spl_token::ID,
+            },
+            instruction::CancelAuction {},
+        );
+        process(&mut self.context, &[ix], &[&self.seller]).await
+    }
+
+    pub async fn set_paused(&mut self, paused: bool) {
+        let ix = build(
+            accounts::SetPaused {
+                admin: self.admin.pubkey(),
+                config: config_pda(),
+            },
+            instruction::SetPaused { paused },
+        );
+        process(&mut self.context, &[ix], &[&self.admin]).await.unwrap();
+    }
+}
+
+/// Signs `instructions` with the context payer and `signers` and processes
+/// them in one transaction.
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Builds a marketplace instruction from its Anchor accounts and arguments.
+pub fn build(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: marketplace::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+/// Builds a marketplace instruction with extra writable remaining accounts,
+/// e.g. creator payment accounts.
+pub fn build_with_remaining(
+    accounts: impl ToAccountMetas,
+    args: impl InstructionData,
+    remaining: &[Pubkey],
+) -> Instruction {
+    let mut ix = build(accounts, args);
+    ix.accounts.extend(remaining.iter().map(|key| AccountMeta::new(*key, false)));
+    ix
+}
+
+/// Asserts that a transaction failed with the program's `error`.
+pub fn assert_error<T: std::fmt::Debug>(result: Result<T, BanksClientError>, error: ErrorCode) {
+    let err = result.expect_err(&format!("expected {error:?}"));
+    match err.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(code, u32::from(error), "expected {error:?}, got error code {code}");
+        }
+        other => panic!("expected {error:?}, got {other:?}"),
+    }
+}
+
+pub fn listing_args(price: u64, quantity: u64) -> instruction::CreateListing {
+    instruction::CreateListing {
+        price,
+        quantity,
+        name: ITEM_NAME.to_string(),
+        dutch_auction: None,
+        expires_at: None,
+        buyer_restriction: None,
+        max_per_buyer: None,
+        metadata: no_metadata(),
+    }
+}
+
+pub fn swap_listing_args(price: u64, quantity: u64) -> instruction::CreateSwapListing {
+    instruction::CreateSwapListing {
+        price,
+        quantity,
+        name: ITEM_NAME.to_string(),
+        dutch_auction: None,
+        expires_at: None,
+        buyer_restriction: None,
+        max_per_buyer: None,
+        metadata: no_metadata(),
+    }
+}
+
+pub fn purchase_args(quantity: u64, unit_price: u64) -> instruction::Purchase {
+    instruction::Purchase {
+        quantity,
+        max_price: unit_price,
+        max_total: unit_price * quantity,
+        proof: vec![],
+    }
+}
+
+pub fn no_metadata() -> ListingMetadata {
+    ListingMetadata {
+        description: String::new(),
+        uri: String::new(),
+        category: Category::Other,
+        tags: vec![],
+    }
+}
+
+pub fn config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &marketplace::ID).0
+}
+
+pub fn fee_vault_pda(payment_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"fee_vault", payment_mint.as_ref()], &marketplace::ID).0
+}
+
+pub fn seller_profile_pda(seller: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"seller_profile", seller.as_ref()], &marketplace::ID).0
+}
+
+pub fn listing_pda(seller: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"listing", seller.as_ref(), nonce.to_le_bytes().as_ref()],
+        &marketplace::ID,
+    )
+    .0
+}
+
+pub fn escrow_pda(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow", owner.as_ref()], &marketplace::ID).0
+}
+
+pub fn royalty_config_pda(item_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"royalty", item_mint.as_ref()], &marketplace::ID).0
+}
+
+pub fn buyer_receipt_pda(listing: &Pubkey, buyer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"buyer_receipt", listing.as_ref(), buyer.as_ref()],
+        &marketplace::ID,
+    )
+    .0
+}
+
+pub fn sale_receipt_pda(listing: &Pubkey, sale_count: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"sale_receipt", listing.as_ref(), sale_count.to_le_bytes().as_ref()],
+        &marketplace::ID,
+    )
+    .0
+}
+
+pub fn offer_pda(buyer: &Pubkey, item_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer", buyer.as_ref(), item_mint.as_ref()], &marketplace::ID).0
+}
+
+pub fn offer_escrow_pda(offer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer_escrow", offer.as_ref()], &marketplace::ID).0
+}
+
+pub fn auction_pda(seller: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"auction", seller.as_ref(), nonce.to_le_bytes().as_ref()],
+        &marketplace::ID,
+    )
+    .0
+}
+
+pub fn bid_escrow_pda(auction: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"bid_escrow", auction.as_ref()], &marketplace::ID).0
+}
+
+async fn create_mint(context: &mut ProgramTestContext) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &context.payer.pubkey(),
+            None,
+            0,
+        )
+        .unwrap(),
+    ];
+    process(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_account3(&spl_token::ID, &account.pubkey(), mint, owner)
+            .unwrap(),
+    ];
+    process(context, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    let ix = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        mint,
+        account,
+        &context.payer.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    process(context, &[ix], &[]).await.unwrap();
+}
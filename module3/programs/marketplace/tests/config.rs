@@ -0,0 +1,62 @@
+mod common;
+
+use anchor_lang::AccountSerialize;
+use common::*;
+use marketplace::{accounts, instruction, MarketplaceConfig};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signer::Signer,
+    system_program,
+};
+
+#[tokio::test]
+async fn migrates_a_legacy_config() {
+    let mut h = Harness::new().await;
+    let config = config_pda();
+    let current: MarketplaceConfig = h.account(config).await;
+
+    // Rewrite the config in the layout before `paused` was appended
+    let mut data = vec![];
+    current.try_serialize(&mut data).unwrap();
+    data.truncate(MarketplaceConfig::LEN - 1);
+    let rent = h.context.banks_client.get_rent().await.unwrap();
+    let legacy = Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: marketplace::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    h.context.set_account(&config, &AccountSharedData::from(legacy));
+    let admin_before = h.lamports(h.admin.pubkey()).await;
+    let config_before = h.lamports(config).await;
+
+    let ix = build(
+        accounts::MigrateConfig {
+            admin: h.admin.pubkey(),
+            config,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateConfig {},
+    );
+    process(&mut h.context, &[ix], &[&h.admin]).await.unwrap();
+
+    // Resized to the current layout, with the admin paying the extra rent
+    let account = h.context.banks_client.get_account(config).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), MarketplaceConfig::LEN);
+    assert_eq!(account.lamports, rent.minimum_balance(MarketplaceConfig::LEN));
+    let top_up = account.lamports - config_before;
+    assert!(top_up > 0);
+    assert_eq!(h.lamports(h.admin.pubkey()).await, admin_before - top_up);
+
+    let migrated: MarketplaceConfig = h.account(config).await;
+    assert_eq!(migrated.admin, current.admin);
+    assert_eq!(migrated.fee_bps, current.fee_bps);
+    assert_eq!(migrated.bump, current.bump);
+    assert!(!migrated.paused);
+
+    // Instructions that load the config work again
+    h.set_paused(true).await;
+    let paused: MarketplaceConfig = h.account(config).await;
+    assert!(paused.paused);
+}
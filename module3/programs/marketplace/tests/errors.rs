@@ -0,0 +1,845 @@
+//! One failing transaction per `ErrorCode` variant.
+
+mod common;
+
+use anchor_lang::AccountSerialize;
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use common::*;
+use marketplace::{
+    accounts, instruction, Auction, BatchPurchase, BuyerRestriction, Creator, DutchAuction, ErrorCode, ListingMetadata,
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    instruction::AccountMeta,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_program,
+};
+
+impl Harness {
+    async fn create_listing_expecting(&mut self, args: instruction::CreateListing, error: ErrorCode) {
+        let accounts = self.create_listing_accounts().await;
+        let result = self.create_listing_with(accounts, args).await;
+        assert_error(result, error);
+    }
+
+    async fn update_config(&mut self, admin: &Keypair, fee_bps: Option<u16>) -> Result<(), BanksClientError> {
+        let ix = build(
+            accounts::UpdateConfig {
+                admin: admin.pubkey(),
+                config: config_pda(),
+            },
+            instruction::UpdateConfig { new_admin: None, fee_bps },
+        );
+        process(&mut self.context, &[ix], &[admin]).await
+    }
+}
+
+fn creator(share_bps: u16) -> Creator {
+    Creator {
+        address: Pubkey::new_unique(),
+        share_bps,
+    }
+}
+
+#[tokio::test]
+async fn invalid_price() {
+    let mut h = Harness::new().await;
+    h.create_listing_expecting(listing_args(0, ITEM_QUANTITY), ErrorCode::InvalidPrice).await;
+}
+
+#[tokio::test]
+async fn invalid_quantity() {
+    let mut h = Harness::new().await;
+    h.create_listing_expecting(listing_args(ITEM_PRICE, 0), ErrorCode::InvalidQuantity).await;
+}
+
+#[tokio::test]
+async fn invalid_name() {
+    let mut h = Harness::new().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.name = String::new();
+    h.create_listing_expecting(args, ErrorCode::InvalidName).await;
+}
+
+#[tokio::test]
+async fn name_too_long() {
+    let mut h = Harness::new().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.name = "x".repeat(marketplace::MAX_NAME_BYTES + 1);
+    h.create_listing_expecting(args, ErrorCode::NameTooLong).await;
+}
+
+#[tokio::test]
+async fn name_too_long_for_an_auction() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let result = h
+        .create_auction_from(instruction::CreateAuction {
+            reserve_price: RESERVE_PRICE,
+            min_bid_increment: 10,
+            start_time: now - 60,
+            end_time: now + 3_600,
+            quantity: 1,
+            name: "x".repeat(marketplace::MAX_NAME_BYTES + 1),
+        })
+        .await;
+    assert_error(result, ErrorCode::NameTooLong);
+}
+
+#[tokio::test]
+async fn listing_not_active() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    h.purchase(listing, ITEM_QUANTITY).await.unwrap();
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::ListingNotActive);
+}
+
+#[tokio::test]
+async fn insufficient_quantity() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    assert_error(h.purchase(listing, ITEM_QUANTITY + 1).await, ErrorCode::InsufficientQuantity);
+}
+
+#[tokio::test]
+async fn invalid_seller() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.cancel_listing_accounts(listing);
+    accounts.seller = h.buyer.pubkey();
+    accounts.seller_token_account = h.buyer_item_account;
+    let ix = build(accounts, instruction::CancelListing {});
+    let result = process(&mut h.context, &[ix], &[&h.buyer]).await;
+
+    assert_error(result, ErrorCode::InvalidSeller);
+}
+
+#[tokio::test]
+async fn invalid_owner() {
+    let mut h = Harness::new().await;
+
+    let mut accounts = h.create_listing_accounts().await;
+    accounts.seller_token_account = h.buyer_item_account;
+    let result = h
+        .create_listing_with(accounts, listing_args(ITEM_PRICE, ITEM_QUANTITY))
+        .await;
+
+    assert_error(result, ErrorCode::InvalidOwner);
+}
+
+#[tokio::test]
+async fn invalid_escrow_owner() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.escrow_token_account = h.seller_item_account;
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::InvalidEscrowOwner);
+}
+
+#[tokio::test]
+async fn numerical_overflow() {
+    let mut h = Harness::new().await;
+    let accounts = h.create_listing_accounts().await;
+    let listing = h
+        .create_listing_with(accounts, listing_args(u64::MAX, ITEM_QUANTITY))
+        .await
+        .unwrap();
+
+    let mut args = purchase_args(2, 1);
+    args.max_price = u64::MAX;
+    args.max_total = u64::MAX;
+    let result = h.purchase_with(h.purchase_accounts(listing), args).await;
+
+    assert_error(result, ErrorCode::NumericalOverflow);
+}
+
+#[tokio::test]
+async fn invalid_buyer() {
+    let mut h = Harness::new().await;
+    let offer = h.create_offer(ITEM_PRICE, 1).await.unwrap();
+
+    let mut accounts = h.cancel_offer_accounts(offer);
+    accounts.buyer = h.seller.pubkey();
+    accounts.buyer_payment_account = h.seller_payment_account;
+    let ix = build(accounts, instruction::CancelOffer {});
+    let result = process(&mut h.context, &[ix], &[&h.seller]).await;
+
+    assert_error(result, ErrorCode::InvalidBuyer);
+}
+
+#[tokio::test]
+async fn invalid_mint() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.item_mint = h.payment_mint;
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::InvalidMint);
+}
+
+#[tokio::test]
+async fn invalid_admin() {
+    let mut h = Harness::new().await;
+    let seller = h.seller.insecure_clone();
+
+    assert_error(h.update_config(&seller, Some(0)).await, ErrorCode::InvalidAdmin);
+}
+
+#[tokio::test]
+async fn invalid_fee_bps() {
+    let mut h = Harness::new().await;
+    let admin = h.admin.insecure_clone();
+
+    assert_error(
+        h.update_config(&admin, Some(marketplace::MAX_FEE_BPS + 1)).await,
+        ErrorCode::InvalidFeeBps,
+    );
+}
+
+#[tokio::test]
+async fn too_many_creators() {
+    let mut h = Harness::new().await;
+    let creators = (0..=marketplace::MAX_CREATORS).map(|_| creator(100)).collect();
+
+    assert_error(h.create_royalty_config(None, creators).await, ErrorCode::TooManyCreators);
+}
+
+#[tokio::test]
+async fn invalid_royalty_bps() {
+    let mut h = Harness::new().await;
+
+    assert_error(
+        h.create_royalty_config(None, vec![creator(0)]).await,
+        ErrorCode::InvalidRoyaltyBps,
+    );
+}
+
+#[tokio::test]
+async fn invalid_royalty_authority() {
+    let mut h = Harness::new().await;
+    let seller = h.seller.insecure_clone();
+
+    assert_error(
+        h.create_royalty_config(Some(&seller), vec![creator(500)]).await,
+        ErrorCode::InvalidRoyaltyAuthority,
+    );
+}
+
+#[tokio::test]
+async fn invalid_royalty_config() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    // Data at the royalty PDA that the marketplace does not own
+    let rent = h.context.banks_client.get_rent().await.unwrap();
+    let impostor = Account {
+        lamports: rent.minimum_balance(8),
+        data: vec![1; 8],
+        owner: system_program::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    h.context
+        .set_account(&royalty_config_pda(&h.item_mint), &AccountSharedData::from(impostor));
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::InvalidRoyaltyConfig);
+}
+
+#[tokio::test]
+async fn missing_creator_account() {
+    let mut h = Harness::new().await;
+    h.create_royalty_config(None, vec![creator(500)]).await.unwrap();
+    let listing = h.create_listing().await;
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::MissingCreatorAccount);
+}
+
+#[tokio::test]
+async fn invalid_auction_times() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+
+    assert_error(h.create_auction(now + 60, now + 60).await, ErrorCode::InvalidAuctionTimes);
+}
+
+#[tokio::test]
+async fn auction_not_active() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    // Settling and cancelling close the auction, so mark it inactive in place
+    let mut state: Auction = h.account(auction).await;
+    state.active = false;
+    let mut data = vec![];
+    state.try_serialize(&mut data).unwrap();
+    let mut account = h.context.banks_client.get_account(auction).await.unwrap().unwrap();
+    account.data[..data.len()].copy_from_slice(&data);
+    h.context.set_account(&auction, &AccountSharedData::from(account));
+
+    assert_error(h.place_bid(auction, RESERVE_PRICE, None).await, ErrorCode::AuctionNotActive);
+}
+
+#[tokio::test]
+async fn auction_not_started() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let auction = h.create_auction(now + 60, now + 3_600).await.unwrap();
+
+    assert_error(h.place_bid(auction, RESERVE_PRICE, None).await, ErrorCode::AuctionNotStarted);
+}
+
+#[tokio::test]
+async fn auction_ended() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    let now = h.now().await;
+    h.set_time(now + 3_600).await;
+
+    assert_error(h.place_bid(auction, RESERVE_PRICE, None).await, ErrorCode::AuctionEnded);
+}
+
+#[tokio::test]
+async fn auction_not_ended() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    h.place_bid(auction, RESERVE_PRICE, None).await.unwrap();
+
+    let winner_token_account = h.buyer_item_account;
+    assert_error(h.settle_auction(auction, winner_token_account).await, ErrorCode::AuctionNotEnded);
+}
+
+#[tokio::test]
+async fn bid_too_low() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+
+    assert_error(h.place_bid(auction, RESERVE_PRICE - 1, None).await, ErrorCode::BidTooLow);
+}
+
+#[tokio::test]
+async fn auction_has_bids() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    h.place_bid(auction, RESERVE_PRICE, None).await.unwrap();
+
+    assert_error(h.cancel_auction(auction).await, ErrorCode::AuctionHasBids);
+}
+
+#[tokio::test]
+async fn no_bids() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    // Without bids the auction's highest bidder is still the default key
+    let item_mint = h.item_mint;
+    let unclaimed = h.create_token_account(&item_mint, &Pubkey::default()).await;
+
+    assert_error(h.settle_auction(auction, unclaimed).await, ErrorCode::NoBids);
+}
+
+#[tokio::test]
+async fn missing_previous_bidder_account() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    h.place_bid(auction, RESERVE_PRICE, None).await.unwrap();
+
+    assert_error(
+        h.place_bid(auction, RESERVE_PRICE * 2, None).await,
+        ErrorCode::MissingPreviousBidderAccount,
+    );
+}
+
+#[tokio::test]
+async fn invalid_dutch_auction() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.dutch_auction = Some(DutchAuction {
+        floor_price: ITEM_PRICE,
+        start_time: now,
+        duration: 3_600,
+    });
+
+    h.create_listing_expecting(args, ErrorCode::InvalidDutchAuction).await;
+}
+
+#[tokio::test]
+async fn price_mismatch() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let result = h
+        .purchase_with(h.purchase_accounts(listing), purchase_args(1, ITEM_PRICE - 1))
+        .await;
+
+    assert_error(result, ErrorCode::PriceMismatch);
+}
+
+#[tokio::test]
+async fn listing_still_active() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let ix = build(
+        accounts::CloseListing {
+            seller: h.seller.pubkey(),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            seller_token_account: h.seller_item_account,
+            item_mint: h.item_mint,
+            token_program: spl_token::ID,
+        },
+        instruction::CloseListing {},
+    );
+    let result = process(&mut h.context, &[ix], &[&h.seller]).await;
+
+    assert_error(result, ErrorCode::ListingStillActive);
+}
+
+#[tokio::test]
+async fn invalid_buyer_payment_mint() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.buyer_payment_account = Some(h.buyer_item_account);
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::InvalidBuyerPaymentMint);
+}
+
+#[tokio::test]
+async fn invalid_seller_payment_mint() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.seller_payment_account = Some(h.seller_item_account);
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::InvalidSellerPaymentMint);
+}
+
+#[tokio::test]
+async fn invalid_seller_payment_owner() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.seller_payment_account = Some(h.buyer_payment_account);
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::InvalidSellerPaymentOwner);
+}
+
+#[tokio::test]
+async fn missing_payment_account() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.buyer_payment_account = None;
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::MissingPaymentAccount);
+}
+
+#[tokio::test]
+async fn invalid_expiry() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.expires_at = Some(now);
+
+    h.create_listing_expecting(args, ErrorCode::InvalidExpiry).await;
+}
+
+#[tokio::test]
+async fn listing_expired() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.expires_at = Some(now + 60);
+    let accounts = h.create_listing_accounts().await;
+    let listing = h.create_listing_with(accounts, args).await.unwrap();
+    h.set_time(now + 60).await;
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::ListingExpired);
+}
+
+#[tokio::test]
+async fn listing_not_expired() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let ix = build(
+        accounts::ExpireListing {
+            cranker: h.buyer.pubkey(),
+            seller: h.seller.pubkey(),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            seller_token_account: h.seller_item_account,
+            item_mint: h.item_mint,
+            token_program: spl_token::ID,
+        },
+        instruction::ExpireListing {},
+    );
+    let result = process(&mut h.context, &[ix], &[&h.buyer]).await;
+
+    assert_error(result, ErrorCode::ListingNotExpired);
+}
+
+#[tokio::test]
+async fn buyer_not_allowed() {
+    let mut h = Harness::new().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.buyer_restriction = Some(BuyerRestriction::Reserved {
+        buyer: h.admin.pubkey(),
+    });
+    let accounts = h.create_listing_accounts().await;
+    let listing = h.create_listing_with(accounts, args).await.unwrap();
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::BuyerNotAllowed);
+}
+
+/// Lists with a cap of one item per buyer.
+async fn create_capped_listing(h: &mut Harness) -> Pubkey {
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.max_per_buyer = Some(1);
+    let accounts = h.create_listing_accounts().await;
+    h.create_listing_with(accounts, args).await.unwrap()
+}
+
+#[tokio::test]
+async fn buyer_limit_exceeded() {
+    let mut h = Harness::new().await;
+    let listing = create_capped_listing(&mut h).await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.buyer_receipt = Some(buyer_receipt_pda(&listing, &h.buyer.pubkey()));
+    let result = h.purchase_with(accounts, purchase_args(2, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::BuyerLimitExceeded);
+}
+
+#[tokio::test]
+async fn missing_buyer_receipt() {
+    let mut h = Harness::new().await;
+    let listing = create_capped_listing(&mut h).await;
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::MissingBuyerReceipt);
+}
+
+fn metadata_with(update: impl FnOnce(&mut ListingMetadata)) -> instruction::CreateListing {
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    update(&mut args.metadata);
+    args
+}
+
+#[tokio::test]
+async fn description_too_long() {
+    let mut h = Harness::new().await;
+    let args = metadata_with(|metadata| {
+        metadata.description = "x".repeat(marketplace::MAX_DESCRIPTION_BYTES + 1)
+    });
+
+    h.create_listing_expecting(args, ErrorCode::DescriptionTooLong).await;
+}
+
+#[tokio::test]
+async fn uri_too_long() {
+    let mut h = Harness::new().await;
+    let args = metadata_with(|metadata| metadata.uri = "x".repeat(marketplace::MAX_URI_BYTES + 1));
+
+    h.create_listing_expecting(args, ErrorCode::UriTooLong).await;
+}
+
+#[tokio::test]
+async fn too_many_tags() {
+    let mut h = Harness::new().await;
+    let args = metadata_with(|metadata| metadata.tags = vec!["tag".to_string(); marketplace::MAX_TAGS + 1]);
+
+    h.create_listing_expecting(args, ErrorCode::TooManyTags).await;
+}
+
+#[tokio::test]
+async fn invalid_tag() {
+    let mut h = Harness::new().await;
+    let args = metadata_with(|metadata| metadata.tags = vec![String::new()]);
+
+    h.create_listing_expecting(args, ErrorCode::InvalidTag).await;
+}
+
+#[tokio::test]
+async fn listing_up_to_date() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let ix = build(h.migrate_listing_accounts(listing).await, instruction::MigrateListing {});
+    let result = process(&mut h.context, &[ix], &[&h.seller]).await;
+
+    assert_error(result, ErrorCode::ListingUpToDate);
+}
+
+#[tokio::test]
+async fn marketplace_paused() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    h.set_paused(true).await;
+
+    h.create_listing_expecting(listing_args(ITEM_PRICE, ITEM_QUANTITY), ErrorCode::MarketplacePaused)
+        .await;
+    assert_error(h.purchase(listing, 1).await, ErrorCode::MarketplacePaused);
+    assert_error(h.create_offer(ITEM_PRICE, 1).await, ErrorCode::MarketplacePaused);
+    let now = h.now().await;
+    assert_error(h.create_auction(now - 60, now + 3_600).await, ErrorCode::MarketplacePaused);
+}
+
+#[tokio::test]
+async fn marketplace_paused_during_an_auction() {
+    let mut h = Harness::new().await;
+    let auction = h.create_open_auction().await;
+    h.place_bid(auction, RESERVE_PRICE, None).await.unwrap();
+    h.set_paused(true).await;
+
+    let buyer_payment_account = h.buyer_payment_account;
+    let result = h.place_bid(auction, RESERVE_PRICE + 10, Some(buyer_payment_account)).await;
+    assert_error(result, ErrorCode::MarketplacePaused);
+
+    // Settling stays open so the winning bid can still be paid out
+    let now = h.now().await;
+    h.set_time(now + 3_601).await;
+    let winner_token_account = h.buyer_item_account;
+    h.settle_auction(auction, winner_token_account).await.unwrap();
+}
+
+#[tokio::test]
+async fn invalid_batch_index() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let mut ix = build(
+        h.purchase_batch_accounts(),
+        instruction::PurchaseBatch {
+            items: vec![BatchPurchase { listing_index: 1, quantity: 1, max_price: ITEM_PRICE }],
+        },
+    );
+    ix.accounts.extend(h.purchase_batch_group(listing));
+    let result = process(&mut h.context, &[ix], &[&h.buyer]).await;
+
+    assert_error(result, ErrorCode::InvalidBatchIndex);
+}
+
+#[tokio::test]
+async fn duplicate_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let result = h
+        .purchase_batch(&[
+            (h.purchase_batch_group(listing), 1, ITEM_PRICE),
+            (h.purchase_batch_group(listing), 1, ITEM_PRICE),
+        ])
+        .await;
+
+    assert_error(result, ErrorCode::DuplicateListing);
+}
+
+#[tokio::test]
+async fn swap_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_swap_listing().await;
+
+    let result = h.purchase(listing, 1).await;
+
+    assert_error(result, ErrorCode::SwapListing);
+}
+
+#[tokio::test]
+async fn not_a_swap_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    let result = h.swap(listing, 1).await;
+
+    assert_error(result, ErrorCode::NotASwapListing);
+}
+
+#[tokio::test]
+async fn invalid_swap_mint() {
+    let mut h = Harness::new().await;
+    let mut accounts = h.create_listing_accounts().await;
+    accounts.payment_mint = Some(h.item_mint);
+
+    let result = h
+        .create_swap_listing_with(accounts, swap_listing_args(ITEM_PRICE, ITEM_QUANTITY))
+        .await;
+
+    assert_error(result, ErrorCode::InvalidSwapMint);
+}
+
+#[tokio::test]
+async fn missing_fee_vault() {
+    let mut h = Harness::new().await;
+    let item_mint = h.item_mint;
+    // Nobody created a fee vault for the item mint, so a sale priced in it
+    // has nowhere to pay its fee
+    let mut accounts = h.create_listing_accounts().await;
+    accounts.payment_mint = Some(item_mint);
+    let listing = h
+        .create_listing_with(accounts, listing_args(ITEM_PRICE, ITEM_QUANTITY))
+        .await
+        .unwrap();
+    let buyer = h.buyer.pubkey();
+    let buyer_payment_account = h.create_token_account(&item_mint, &buyer).await;
+    h.mint_to(&item_mint, &buyer_payment_account, ITEM_PRICE).await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.buyer_payment_account = Some(buyer_payment_account);
+    accounts.seller_payment_account = Some(h.seller_item_account);
+    accounts.payment_mint = Some(item_mint);
+    accounts.fee_vault = Some(fee_vault_pda(&item_mint));
+    let result = h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await;
+
+    assert_error(result, ErrorCode::MissingFeeVault);
+}
+
+#[tokio::test]
+async fn invalid_bid_increment() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+
+    let result = h.create_auction_with(0, now - 60, now + 3_600).await;
+
+    assert_error(result, ErrorCode::InvalidBidIncrement);
+}
+
+/// Error codes are part of the program's interface, so variants must only
+/// ever be appended. `BumpSeedNotInHashMap` only appears here: Anchor always
+/// records the bump of a `bump`-constrained account, so no transaction can
+/// reach it. Neither can `OfferNotActive` any more, as offers are closed once
+/// filled or cancelled.
+#[tokio::test]
+async fn token_program_mismatch() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    // The item mint belongs to SPL Token, not Token-2022
+    let mut group = h.purchase_batch_group(listing);
+    group[11] = AccountMeta::new_readonly(spl_token_2022::ID, false);
+    let result = h.purchase_batch(&[(group, 1, ITEM_PRICE)]).await;
+
+    assert_error(result, ErrorCode::TokenProgramMismatch);
+}
+
+#[tokio::test]
+async fn config_up_to_date() {
+    let mut h = Harness::new().await;
+
+    let ix = build(
+        accounts::MigrateConfig {
+            admin: h.admin.pubkey(),
+            config: config_pda(),
+            system_program: system_program::ID,
+        },
+        instruction::MigrateConfig {},
+    );
+    let result = process(&mut h.context, &[ix], &[&h.admin]).await;
+
+    assert_error(result, ErrorCode::ConfigUpToDate);
+}
+
+#[tokio::test]
+async fn listing_needs_migration() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    // A listing on an older version, as a future layout change leaves them
+    let mut account = h.context.banks_client.get_account(listing).await.unwrap().unwrap();
+    account.data[8] = marketplace::LISTING_VERSION - 1;
+    h.context.set_account(&listing, &AccountSharedData::from(account));
+
+    assert_error(h.purchase(listing, 1).await, ErrorCode::ListingNeedsMigration);
+}
+
+#[test]
+fn error_codes_are_stable() {
+    let variants = [
+        ErrorCode::InvalidPrice,
+        ErrorCode::InvalidQuantity,
+        ErrorCode::InvalidName,
+        ErrorCode::NameTooLong,
+        ErrorCode::ListingNotActive,
+        ErrorCode::InsufficientQuantity,
+        ErrorCode::InvalidSeller,
+        ErrorCode::InvalidOwner,
+        ErrorCode::InvalidEscrowOwner,
+        ErrorCode::NumericalOverflow,
+        ErrorCode::BumpSeedNotInHashMap,
+        ErrorCode::OfferNotActive,
+        ErrorCode::InvalidBuyer,
+        ErrorCode::InvalidMint,
+        ErrorCode::InvalidAdmin,
+        ErrorCode::InvalidFeeBps,
+        ErrorCode::TooManyCreators,
+        ErrorCode::InvalidRoyaltyBps,
+        ErrorCode::InvalidRoyaltyAuthority,
+        ErrorCode::InvalidRoyaltyConfig,
+        ErrorCode::MissingCreatorAccount,
+        ErrorCode::InvalidAuctionTimes,
+        ErrorCode::AuctionNotActive,
+        ErrorCode::AuctionNotStarted,
+        ErrorCode::AuctionEnded,
+        ErrorCode::AuctionNotEnded,
+        ErrorCode::BidTooLow,
+        ErrorCode::AuctionHasBids,
+        ErrorCode::NoBids,
+        ErrorCode::MissingPreviousBidderAccount,
+        ErrorCode::InvalidDutchAuction,
+        ErrorCode::PriceMismatch,
+        ErrorCode::ListingStillActive,
+        ErrorCode::InvalidBuyerPaymentMint,
+        ErrorCode::InvalidSellerPaymentMint,
+        ErrorCode::InvalidSellerPaymentOwner,
+        ErrorCode::MissingPaymentAccount,
+        ErrorCode::InvalidExpiry,
+        ErrorCode::ListingExpired,
+        ErrorCode::ListingNotExpired,
+        ErrorCode::BuyerNotAllowed,
+        ErrorCode::BuyerLimitExceeded,
+        ErrorCode::MissingBuyerReceipt,
+        ErrorCode::DescriptionTooLong,
+        ErrorCode::UriTooLong,
+        ErrorCode::TooManyTags,
+        ErrorCode::InvalidTag,
+        ErrorCode::ListingUpToDate,
+        ErrorCode::MarketplacePaused,
+        ErrorCode::InvalidBatchIndex,
+        ErrorCode::DuplicateListing,
+        ErrorCode::SwapListing,
+        ErrorCode::NotASwapListing,
+        ErrorCode::InvalidSwapMint,
+        ErrorCode::MissingFeeVault,
+        ErrorCode::InvalidBidIncrement,
+        ErrorCode::ConfigUpToDate,
+        ErrorCode::TokenProgramMismatch,
+        ErrorCode::ListingNeedsMigration,
+    ];
+
+    for (offset, variant) in variants.into_iter().enumerate() {
+        assert_eq!(
+            u32::from(variant),
+            anchor_lang::error::ERROR_CODE_OFFSET + offset as u32,
+            "{variant:?} moved"
+        );
+    }
+}
+
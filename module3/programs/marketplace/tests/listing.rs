@@ -0,0 +1,598 @@
+mod common;
+
+use anchor_lang::{AnchorSerialize, Discriminator};
+use anchor_spl::token::spl_token::{self, solana_program::program_pack::Pack};
+use common::*;
+use marketplace::{
+    accounts, instruction, BuyerReceipt, Creator, DutchAuction, Listing, ListingMetadata, ListingV0,
+    MarketplaceConfig, PaymentKind, SaleReceipt, LISTING_VERSION,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    instruction::AccountMeta,
+    pubkey::Pubkey,
+    signer::Signer,
+};
+
+#[tokio::test]
+async fn creates_a_listing() {
+    let mut h = Harness::new().await;
+
+    let listing = h.create_listing().await;
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.version, LISTING_VERSION);
+    assert_eq!(state.seller, h.seller.pubkey());
+    assert_eq!(state.nonce, 0);
+    assert_eq!(state.payment_kind, PaymentKind::Spl);
+    assert_eq!(state.payment_mint, h.payment_mint);
+    assert_eq!(state.price, ITEM_PRICE);
+    assert_eq!(state.quantity, ITEM_QUANTITY);
+    assert_eq!(state.name, ITEM_NAME);
+    assert!(state.active);
+
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, ITEM_QUANTITY);
+    assert_eq!(
+        h.token_balance(h.seller_item_account).await,
+        STARTING_TOKENS - ITEM_QUANTITY
+    );
+    assert_eq!(h.next_listing_nonce(&h.seller.pubkey()).await, 1);
+}
+
+#[tokio::test]
+async fn purchases_from_a_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    let quantity = 2;
+
+    h.purchase(listing, quantity).await.unwrap();
+
+    let total = ITEM_PRICE * quantity;
+    let fee = total * FEE_BPS as u64 / 10_000;
+    assert_eq!(h.token_balance(h.buyer_item_account).await, quantity);
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, ITEM_QUANTITY - quantity);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, total - fee);
+    assert_eq!(h.token_balance(fee_vault_pda(&h.payment_mint)).await, fee);
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.quantity, ITEM_QUANTITY - quantity);
+    assert_eq!(state.sale_count, 1);
+    assert_eq!(state.units_sold, quantity);
+    assert_eq!(state.total_volume, total);
+    assert!(state.active);
+}
+
+#[tokio::test]
+async fn sells_out_a_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+
+    h.purchase(listing, ITEM_QUANTITY).await.unwrap();
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.quantity, 0);
+    assert!(!state.active);
+}
+
+#[tokio::test]
+async fn purchases_a_listing_priced_in_lamports() {
+    let mut h = Harness::new().await;
+    let mut accounts = h.create_listing_accounts().await;
+    accounts.payment_mint = None;
+    let listing = h
+        .create_listing_with(accounts, listing_args(ITEM_PRICE, ITEM_QUANTITY))
+        .await
+        .unwrap();
+
+    let seller_before = h.lamports(h.seller.pubkey()).await;
+    let config_before = h.lamports(config_pda()).await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.buyer_payment_account = None;
+    accounts.seller_payment_account = None;
+    accounts.payment_mint = None;
+    accounts.fee_vault = None;
+    accounts.payment_token_program = None;
+    h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await.unwrap();
+
+    let fee = ITEM_PRICE * FEE_BPS as u64 / 10_000;
+    assert_eq!(h.lamports(h.seller.pubkey()).await, seller_before + ITEM_PRICE - fee);
+    assert_eq!(h.lamports(config_pda()).await, config_before + fee);
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 1);
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.payment_kind, PaymentKind::Native);
+    assert_eq!(state.payment_mint, Default::default());
+}
+
+#[tokio::test]
+async fn purchases_without_a_fee_vault_when_there_is_no_fee() {
+    let mut h = Harness::new().await;
+    let ix = build(
+        accounts::UpdateConfig {
+            admin: h.admin.pubkey(),
+            config: config_pda(),
+        },
+        instruction::UpdateConfig { new_admin: None, fee_bps: Some(0) },
+    );
+    process(&mut h.context, &[ix], &[&h.admin]).await.unwrap();
+    let listing = h.create_listing().await;
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.fee_vault = None;
+    h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await.unwrap();
+
+    assert_eq!(h.token_balance(h.seller_payment_account).await, ITEM_PRICE);
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 1);
+}
+
+#[tokio::test]
+async fn records_a_sale_receipt() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    let sale_receipt = sale_receipt_pda(&listing, 0);
+
+    let mut accounts = h.purchase_accounts(listing);
+    accounts.sale_receipt = Some(sale_receipt);
+    h.purchase_with(accounts, purchase_args(1, ITEM_PRICE)).await.unwrap();
+
+    let receipt: marketplace::SaleReceipt = h.account(sale_receipt).await;
+    assert_eq!(receipt.listing, listing);
+    assert_eq!(receipt.buyer, h.buyer.pubkey());
+    assert_eq!(receipt.quantity, 1);
+    assert_eq!(receipt.unit_price, ITEM_PRICE);
+}
+
+#[tokio::test]
+async fn cancels_a_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    h.purchase(listing, 2).await.unwrap();
+
+    h.cancel_listing(listing).await.unwrap();
+
+    assert!(!h.account_exists(listing).await);
+    assert!(!h.account_exists(escrow_pda(&listing)).await);
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS - 2);
+}
+
+#[tokio::test]
+async fn cancels_a_listing_with_tokens_sent_to_its_escrow() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    let item_mint = h.item_mint;
+    h.mint_to(&item_mint, &escrow_pda(&listing), 3).await;
+
+    h.cancel_listing(listing).await.unwrap();
+
+    assert!(!h.account_exists(listing).await);
+    assert!(!h.account_exists(escrow_pda(&listing)).await);
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS + 3);
+}
+
+#[tokio::test]
+async fn closes_a_sold_out_listing_with_tokens_sent_to_its_escrow() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    h.purchase(listing, ITEM_QUANTITY).await.unwrap();
+    let item_mint = h.item_mint;
+    h.mint_to(&item_mint, &escrow_pda(&listing), 3).await;
+
+    let ix = build(
+        accounts::CloseListing {
+            seller: h.seller.pubkey(),
+            listing,
+            escrow_token_account: escrow_pda(&listing),
+            seller_token_account: h.seller_item_account,
+            item_mint,
+            token_program: spl_token::ID,
+        },
+        instruction::CloseListing {},
+    );
+    process(&mut h.context, &[ix], &[&h.seller]).await.unwrap();
+
+    assert!(!h.account_exists(listing).await);
+    assert!(!h.account_exists(escrow_pda(&listing)).await);
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS - ITEM_QUANTITY + 3);
+}
+
+#[tokio::test]
+async fn allows_cancelling_while_paused() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    h.set_paused(true).await;
+
+    h.cancel_listing(listing).await.unwrap();
+
+    let config: MarketplaceConfig = h.account(config_pda()).await;
+    assert!(config.paused);
+    assert!(!h.account_exists(listing).await);
+}
+
+#[tokio::test]
+async fn relists_under_the_next_nonce() {
+    let mut h = Harness::new().await;
+    let first = h.create_listing().await;
+    h.cancel_listing(first).await.unwrap();
+
+    let second = h.create_listing().await;
+
+    assert_ne!(first, second);
+    assert_eq!(second, listing_pda(&h.seller.pubkey(), 1));
+    let state: Listing = h.account(second).await;
+    assert_eq!(state.nonce, 1);
+}
+
+#[tokio::test]
+async fn purchases_from_several_listings_at_once() {
+    let mut h = Harness::new().await;
+    let first = h.create_listing().await;
+    let second = h.create_listing().await;
+
+    h.purchase_batch(&[
+        (h.purchase_batch_group(first), 2, ITEM_PRICE),
+        (h.purchase_batch_group(second), ITEM_QUANTITY, ITEM_PRICE),
+    ])
+    .await
+    .unwrap();
+
+    let total = ITEM_PRICE * (2 + ITEM_QUANTITY);
+    let fee = ITEM_PRICE * 2 * FEE_BPS as u64 / 10_000 + ITEM_PRICE * ITEM_QUANTITY * FEE_BPS as u64 / 10_000;
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 2 + ITEM_QUANTITY);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, total - fee);
+    assert_eq!(h.token_balance(fee_vault_pda(&h.payment_mint)).await, fee);
+
+    let first: Listing = h.account(first).await;
+    assert_eq!(first.quantity, ITEM_QUANTITY - 2);
+    assert_eq!(first.sale_count, 1);
+    assert!(first.active);
+    let second: Listing = h.account(second).await;
+    assert_eq!(second.quantity, 0);
+    assert_eq!(second.units_sold, ITEM_QUANTITY);
+    assert!(!second.active);
+}
+
+#[tokio::test]
+async fn purchases_a_batch_mixing_payment_kinds() {
+    let mut h = Harness::new().await;
+    let spl = h.create_listing().await;
+    let mut accounts = h.create_listing_accounts().await;
+    accounts.payment_mint = None;
+    let native = h
+        .create_listing_with(accounts, listing_args(ITEM_PRICE, ITEM_QUANTITY))
+        .await
+        .unwrap();
+
+    // Everything SPL-specific, including the payment token program, is absent
+    let mut native_group = h.purchase_batch_group(native);
+    for index in [6, 7, 8, 9, 12] {
+        native_group[index] = AccountMeta::new_readonly(marketplace::ID, false);
+    }
+    let seller_before = h.lamports(h.seller.pubkey()).await;
+
+    h.purchase_batch(&[
+        (h.purchase_batch_group(spl), 1, ITEM_PRICE),
+        (native_group, 1, ITEM_PRICE),
+    ])
+    .await
+    .unwrap();
+
+    let fee = ITEM_PRICE * FEE_BPS as u64 / 10_000;
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 2);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, ITEM_PRICE - fee);
+    assert_eq!(h.lamports(h.seller.pubkey()).await, seller_before + ITEM_PRICE - fee);
+}
+
+#[tokio::test]
+async fn caps_batch_purchases_per_buyer() {
+    let mut h = Harness::new().await;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.max_per_buyer = Some(2);
+    let accounts = h.create_listing_accounts().await;
+    let listing = h.create_listing_with(accounts, args).await.unwrap();
+    let buyer_receipt = buyer_receipt_pda(&listing, &h.buyer.pubkey());
+    let mut group = h.purchase_batch_group(listing);
+    group[10] = AccountMeta::new(buyer_receipt, false);
+
+    // The first purchase creates the receipt
+    h.purchase_batch(&[(group.clone(), 1, ITEM_PRICE)]).await.unwrap();
+    let receipt: BuyerReceipt = h.account(buyer_receipt).await;
+    assert_eq!(receipt.listing, listing);
+    assert_eq!(receipt.buyer, h.buyer.pubkey());
+    assert_eq!(receipt.quantity, 1);
+
+    // Later ones count towards the same cap
+    let result = h.purchase_batch(&[(group.clone(), 2, ITEM_PRICE)]).await;
+    assert_error(result, marketplace::ErrorCode::BuyerLimitExceeded);
+    h.refresh_blockhash().await;
+    h.purchase_batch(&[(group, 1, ITEM_PRICE)]).await.unwrap();
+    let receipt: BuyerReceipt = h.account(buyer_receipt).await;
+    assert_eq!(receipt.quantity, 2);
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 2);
+}
+
+#[tokio::test]
+async fn rolls_back_the_whole_batch_when_one_purchase_fails() {
+    let mut h = Harness::new().await;
+    let first = h.create_listing().await;
+    let second = h.create_listing().await;
+
+    let result = h
+        .purchase_batch(&[
+            (h.purchase_batch_group(first), 2, ITEM_PRICE),
+            (h.purchase_batch_group(second), 1, ITEM_PRICE - 1),
+        ])
+        .await;
+
+    assert_error(result, marketplace::ErrorCode::PriceMismatch);
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 0);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS);
+    let first: Listing = h.account(first).await;
+    assert_eq!(first.quantity, ITEM_QUANTITY);
+    assert_eq!(first.sale_count, 0);
+}
+
+#[tokio::test]
+async fn swaps_payment_tokens_for_items() {
+    let mut h = Harness::new().await;
+    let listing = h.create_swap_listing().await;
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.payment_kind, PaymentKind::Swap);
+    assert_eq!(state.payment_mint, h.payment_mint);
+
+    let quantity = 2;
+    h.swap(listing, quantity).await.unwrap();
+
+    // The seller is paid in full, with no protocol fee
+    let total = ITEM_PRICE * quantity;
+    assert_eq!(h.token_balance(h.buyer_item_account).await, quantity);
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, ITEM_QUANTITY - quantity);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, total);
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.quantity, ITEM_QUANTITY - quantity);
+    assert_eq!(state.sale_count, 1);
+    assert_eq!(state.units_sold, quantity);
+    assert_eq!(state.total_volume, total);
+    assert!(state.active);
+}
+
+#[tokio::test]
+async fn records_a_sale_receipt_on_a_swap() {
+    let mut h = Harness::new().await;
+    let listing = h.create_swap_listing().await;
+    let sale_receipt = sale_receipt_pda(&listing, 0);
+
+    let quantity = 2;
+    let mut accounts = h.swap_accounts(listing);
+    accounts.sale_receipt = Some(sale_receipt);
+    let ix = build(
+        accounts,
+        instruction::Swap {
+            quantity,
+            max_price: ITEM_PRICE,
+            proof: vec![],
+        },
+    );
+    process(&mut h.context, &[ix], &[&h.buyer]).await.unwrap();
+
+    let total = ITEM_PRICE * quantity;
+    assert_eq!(h.token_balance(h.seller_payment_account).await, total);
+    assert_eq!(h.token_balance(fee_vault_pda(&h.payment_mint)).await, 0);
+
+    let receipt: SaleReceipt = h.account(sale_receipt).await;
+    assert_eq!(receipt.listing, listing);
+    assert_eq!(receipt.buyer, h.buyer.pubkey());
+    assert_eq!(receipt.quantity, quantity);
+    assert_eq!(receipt.unit_price, ITEM_PRICE);
+}
+
+#[tokio::test]
+async fn pays_royalties_to_each_creator_on_a_purchase() {
+    let mut h = Harness::new().await;
+    let creators = [Pubkey::new_unique(), Pubkey::new_unique()];
+    h.create_royalty_config(
+        None,
+        vec![
+            Creator { address: creators[0], share_bps: 500 },
+            Creator { address: creators[1], share_bps: 250 },
+        ],
+    )
+    .await
+    .unwrap();
+    let payment_mint = h.payment_mint;
+    let mut creator_accounts = vec![];
+    for creator in &creators {
+        creator_accounts.push(h.create_token_account(&payment_mint, creator).await);
+    }
+    let listing = h.create_listing().await;
+
+    let quantity = 2;
+    let ix = build_with_remaining(
+        h.purchase_accounts(listing),
+        purchase_args(quantity, ITEM_PRICE),
+        &creator_accounts,
+    );
+    process(&mut h.context, &[ix], &[&h.buyer]).await.unwrap();
+
+    // Each creator gets their share of the total; the seller gets the rest
+    // after the fee
+    let total = ITEM_PRICE * quantity;
+    let fee = total * FEE_BPS as u64 / 10_000;
+    let royalties = [total * 500 / 10_000, total * 250 / 10_000];
+    assert_eq!(h.token_balance(creator_accounts[0]).await, royalties[0]);
+    assert_eq!(h.token_balance(creator_accounts[1]).await, royalties[1]);
+    assert_eq!(h.token_balance(fee_vault_pda(&h.payment_mint)).await, fee);
+    assert_eq!(
+        h.token_balance(h.seller_payment_account).await,
+        total - fee - royalties[0] - royalties[1]
+    );
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+}
+
+#[tokio::test]
+async fn lowers_a_dutch_auction_price_over_time() {
+    let mut h = Harness::new().await;
+    let now = h.now().await;
+    let floor_price = ITEM_PRICE / 2;
+    let mut args = listing_args(ITEM_PRICE, ITEM_QUANTITY);
+    args.dutch_auction = Some(DutchAuction {
+        floor_price,
+        start_time: now,
+        duration: 1_000,
+    });
+    let accounts = h.create_listing_accounts().await;
+    let listing = h.create_listing_with(accounts, args).await.unwrap();
+
+    // Halfway through, the price is halfway to the floor; the buyer pays that
+    // even though they would have paid the start price
+    h.set_time(now + 500).await;
+    h.purchase(listing, 1).await.unwrap();
+    let paid = ITEM_PRICE - (ITEM_PRICE - floor_price) / 2;
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - paid);
+
+    // Past the end it stays at the floor
+    h.set_time(now + 5_000).await;
+    h.refresh_blockhash().await;
+    h.purchase(listing, 1).await.unwrap();
+    assert_eq!(
+        h.token_balance(h.buyer_payment_account).await,
+        STARTING_TOKENS - paid - floor_price
+    );
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.total_volume, paid + floor_price);
+}
+
+#[tokio::test]
+async fn updates_a_listing() {
+    let mut h = Harness::new().await;
+    let listing = h.create_listing().await;
+    let metadata = ListingMetadata {
+        description: "Now with a description".to_string(),
+        ..no_metadata()
+    };
+    let (seller, seller_token_account, item_mint) = (h.seller.pubkey(), h.seller_item_account, h.item_mint);
+    let update = |price, deposit, withdraw, metadata| {
+        build(
+            accounts::UpdateListing {
+                seller,
+                listing,
+                escrow_token_account: escrow_pda(&listing),
+                seller_token_account,
+                item_mint,
+                token_program: spl_token::ID,
+            },
+            instruction::UpdateListing {
+                price,
+                deposit,
+                withdraw,
+                metadata,
+            },
+        )
+    };
+
+    let ix = update(Some(ITEM_PRICE * 2), 3, 0, Some(metadata.clone()));
+    process(&mut h.context, &[ix], &[&h.seller]).await.unwrap();
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.price, ITEM_PRICE * 2);
+    assert_eq!(state.quantity, ITEM_QUANTITY + 3);
+    assert_eq!(state.metadata, metadata);
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, ITEM_QUANTITY + 3);
+
+    // Withdrawing everything leaves the listing inactive
+    let ix = update(None, 0, ITEM_QUANTITY + 3, None);
+    process(&mut h.context, &[ix], &[&h.seller]).await.unwrap();
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.price, ITEM_PRICE * 2);
+    assert_eq!(state.quantity, 0);
+    assert!(!state.active);
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, 0);
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS);
+}
+
+/// Stores a listing of `quantity` items and its escrow as the program's
+/// first deployment created them: in `ListingV0`'s layout, at the address
+/// derived from the listing's name.
+async fn create_legacy_listing(h: &mut Harness, quantity: u64) -> Pubkey {
+    let seller = h.seller.pubkey();
+    let (listing, bump) = Pubkey::find_program_address(
+        &[b"listing", seller.as_ref(), ITEM_NAME.as_bytes()],
+        &marketplace::ID,
+    );
+    let rent = h.context.banks_client.get_rent().await.unwrap();
+
+    let mut data = Listing::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(seller.as_ref());
+    data.extend_from_slice(&ITEM_PRICE.to_le_bytes());
+    data.extend_from_slice(&quantity.to_le_bytes());
+    ITEM_NAME.to_string().serialize(&mut data).unwrap();
+    data.push(1); // active
+    data.push(bump);
+    data.resize(ListingV0::LEN, 0);
+    let account = Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: marketplace::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    h.context.set_account(&listing, &AccountSharedData::from(account));
+
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: h.item_mint,
+        owner: listing,
+        amount: quantity,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+    let escrow = Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: spl_token::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    h.context.set_account(&escrow_pda(&listing), &AccountSharedData::from(escrow));
+
+    listing
+}
+
+#[tokio::test]
+async fn migrates_a_legacy_listing() {
+    let mut h = Harness::new().await;
+    let legacy = create_legacy_listing(&mut h, 3).await;
+    let accounts = h.migrate_listing_accounts(legacy).await;
+    let listing = accounts.listing;
+
+    let ix = build(accounts, instruction::MigrateListing {});
+    process(&mut h.context, &[ix], &[&h.seller]).await.unwrap();
+
+    // The items move to a listing under the seller's next nonce, and the
+    // old listing and escrow are closed
+    assert_eq!(listing, listing_pda(&h.seller.pubkey(), 0));
+    assert!(!h.account_exists(legacy).await);
+    assert!(!h.account_exists(escrow_pda(&legacy)).await);
+    assert_eq!(h.token_balance(escrow_pda(&listing)).await, 3);
+
+    let state: Listing = h.account(listing).await;
+    assert_eq!(state.version, LISTING_VERSION);
+    assert_eq!(state.seller, h.seller.pubkey());
+    assert_eq!(state.nonce, 0);
+    assert_eq!(state.payment_kind, PaymentKind::Spl);
+    assert_eq!(state.payment_mint, h.payment_mint);
+    assert_eq!(state.price, ITEM_PRICE);
+    assert_eq!(state.quantity, 3);
+    assert_eq!(state.name, ITEM_NAME);
+    assert!(state.active);
+
+    // It then sells like any other listing
+    h.purchase(listing, 3).await.unwrap();
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 3);
+    let state: Listing = h.account(listing).await;
+    assert!(!state.active);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use anchor_spl::token_2022::spl_token_2022;
+use common::*;
+use marketplace::{instruction, Offer};
+use solana_sdk::signer::Signer;
+
+#[tokio::test]
+async fn fills_an_offer_and_closes_it() {
+    let mut h = Harness::new().await;
+    let offer = h.create_offer(ITEM_PRICE, 3).await.unwrap();
+
+    h.accept_offer(offer, 1).await.unwrap();
+    let state: Offer = h.account(offer).await;
+    assert_eq!(state.quantity, 2);
+    assert!(state.active);
+
+    let buyer_before = h.lamports(h.buyer.pubkey()).await;
+    let rent = h.lamports(offer).await + h.lamports(offer_escrow_pda(&offer)).await;
+    h.accept_offer(offer, 2).await.unwrap();
+
+    let total = ITEM_PRICE * 3;
+    let fee = total * FEE_BPS as u64 / 10_000;
+    assert!(!h.account_exists(offer).await);
+    assert!(!h.account_exists(offer_escrow_pda(&offer)).await);
+    assert_eq!(h.lamports(h.buyer.pubkey()).await, buyer_before + rent);
+    assert_eq!(h.token_balance(h.buyer_item_account).await, 3);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+    assert_eq!(h.token_balance(h.seller_payment_account).await, total - fee);
+}
+
+#[tokio::test]
+async fn refunds_tokens_sent_to_a_filled_offers_escrow() {
+    let mut h = Harness::new().await;
+    let offer = h.create_offer(ITEM_PRICE, 1).await.unwrap();
+    let payment_mint = h.payment_mint;
+    h.mint_to(&payment_mint, &offer_escrow_pda(&offer), 7).await;
+
+    h.accept_offer(offer, 1).await.unwrap();
+
+    assert!(!h.account_exists(offer_escrow_pda(&offer)).await);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - ITEM_PRICE + 7);
+}
+
+#[tokio::test]
+async fn cancels_an_offer_and_offers_again() {
+    let mut h = Harness::new().await;
+    let offer = h.create_offer(ITEM_PRICE, 3).await.unwrap();
+    h.accept_offer(offer, 1).await.unwrap();
+
+    let buyer_before = h.lamports(h.buyer.pubkey()).await;
+    let rent = h.lamports(offer).await + h.lamports(offer_escrow_pda(&offer)).await;
+    h.cancel_offer(offer).await.unwrap();
+
+    assert!(!h.account_exists(offer).await);
+    assert!(!h.account_exists(offer_escrow_pda(&offer)).await);
+    assert_eq!(h.lamports(h.buyer.pubkey()).await, buyer_before + rent);
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - ITEM_PRICE);
+
+    // The same buyer can offer on the same mint again
+    h.create_offer(ITEM_PRICE * 2, 1).await.unwrap();
+    let state: Offer = h.account(offer).await;
+    assert_eq!(state.price, ITEM_PRICE * 2);
+    assert_eq!(state.quantity, 1);
+}
+
+#[tokio::test]
+async fn delivers_the_full_quantity_of_a_token_2022_item_with_a_transfer_fee() {
+    let mut h = Harness::new().await;
+    h.use_token_2022_items(100).await;
+    let quantity = 100;
+    let offer = h.create_offer(ITEM_PRICE, quantity).await.unwrap();
+
+    let mut accounts = h.accept_offer_accounts(offer);
+    accounts.token_program = spl_token_2022::ID;
+    let ix = build(accounts, instruction::AcceptOffer { quantity });
+    process(&mut h.context, &[ix], &[&h.seller]).await.unwrap();
+
+    // The seller sends 102 items, whose rounded-up 1% fee is 2, so the
+    // buyer receives every item they paid for
+    assert_eq!(h.token_balance(h.buyer_item_account).await, quantity);
+    assert_eq!(h.token_balance(h.seller_item_account).await, STARTING_TOKENS - 102);
+    let total = ITEM_PRICE * quantity;
+    assert_eq!(h.token_balance(h.buyer_payment_account).await, STARTING_TOKENS - total);
+}
@@ -3,11 +3,17 @@ import { Program } from "@coral-xyz/anchor";
 import { Marketplace } from "../target/types/marketplace";
 import { 
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  ExtensionType,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  getMintLen,
   createMint,
   mintTo,
   createAccount,
   getAccount
 } from "@solana/spl-token";
+import { keccak_256 } from "@noble/hashes/sha3";
 import { assert } from "chai";
 
 describe("marketplace", () => {
@@ -18,21 +24,61 @@ describe("marketplace", () => {
   const program = anchor.workspace.Marketplace as Program<Marketplace>;
   const seller = anchor.web3.Keypair.generate();
   const buyer = anchor.web3.Keypair.generate();
+  const admin = anchor.web3.Keypair.generate();
+  const bidder = anchor.web3.Keypair.generate();
   let itemMint: anchor.web3.PublicKey;
   let paymentMint: anchor.web3.PublicKey;
   let sellerItemAccount: anchor.web3.PublicKey;
   let buyerItemAccount: anchor.web3.PublicKey;
   let sellerPaymentAccount: anchor.web3.PublicKey;
   let buyerPaymentAccount: anchor.web3.PublicKey;
+  let sellerProfilePda: anchor.web3.PublicKey;
   let listingPda: anchor.web3.PublicKey;
   let listingBump: number;
   let escrowPda: anchor.web3.PublicKey;
   let escrowBump: number;
+  let offerPda: anchor.web3.PublicKey;
+  let offerEscrowPda: anchor.web3.PublicKey;
+  let configPda: anchor.web3.PublicKey;
+  let feeVaultPda: anchor.web3.PublicKey;
+  let adminPaymentAccount: anchor.web3.PublicKey;
+  let royaltyConfigPda: anchor.web3.PublicKey;
+  let bidderItemAccount: anchor.web3.PublicKey;
+  let bidderPaymentAccount: anchor.web3.PublicKey;
   
   const itemName = "Test Item";
   const itemPrice = 100;
   const itemQuantity = 5;
   const purchaseQuantity = 2;
+  const offerPrice = 80;
+  const offerQuantity = 3;
+  const acceptQuantity = 2;
+  const feeBps = 250;
+  const feeOf = (amount: number) => Math.floor((amount * feeBps) / 10_000);
+  const royaltyBps = 500;
+  const royaltyOf = (amount: number) => Math.floor((amount * royaltyBps) / 10_000);
+  const noMetadata = { description: "", uri: "", category: { other: {} }, tags: [] };
+  const itemMetadata = {
+    description: "A test item for the marketplace",
+    uri: "https://example.com/item.json",
+    category: { collectible: {} },
+    tags: ["test", "item"],
+  };
+
+  // Listing and escrow PDAs for the seller's next listing nonce
+  const nextListing = async () => {
+    const profile = await program.account.sellerProfile.fetchNullable(sellerProfilePda);
+    const nonce = profile ? profile.listingNonce : new anchor.BN(0);
+    const [listing] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("listing"), seller.publicKey.toBuffer(), nonce.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [escrow] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), listing.toBuffer()],
+      program.programId
+    );
+    return [listing, escrow];
+  };
 
   before(async () => {
     // Airdrop SOL to seller and buyer
@@ -42,6 +88,12 @@ describe("marketplace", () => {
     await provider.connection.confirmTransaction(
       await provider.connection.requestAirdrop(buyer.publicKey, 2 * anchor.web3.LAMPORTS_PER_SOL)
     );
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(admin.publicKey, 2 * anchor.web3.LAMPORTS_PER_SOL)
+    );
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(bidder.publicKey, 2 * anchor.web3.LAMPORTS_PER_SOL)
+    );
 
     // Create item mint
     itemMint = await createMint(
@@ -90,6 +142,27 @@ describe("marketplace", () => {
       buyer.publicKey
     );
 
+    adminPaymentAccount = await createAccount(
+      provider.connection,
+      admin,
+      paymentMint,
+      admin.publicKey
+    );
+
+    bidderItemAccount = await createAccount(
+      provider.connection,
+      bidder,
+      itemMint,
+      bidder.publicKey
+    );
+
+    bidderPaymentAccount = await createAccount(
+      provider.connection,
+      bidder,
+      paymentMint,
+      bidder.publicKey
+    );
+
     // Mint tokens
     await mintTo(
       provider.connection,
@@ -109,12 +182,30 @@ describe("marketplace", () => {
       itemPrice * itemQuantity * 2 // Enough to buy all items
     );
 
+    await mintTo(
+      provider.connection,
+      buyer,
+      paymentMint,
+      bidderPaymentAccount,
+      buyer.publicKey,
+      itemPrice * itemQuantity
+    );
+
     // Derive PDAs
+    [sellerProfilePda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("seller_profile"),
+        seller.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+
+    // The seller's first listing uses nonce 0
     [listingPda, listingBump] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from("listing"),
         seller.publicKey.toBuffer(),
-        Buffer.from(itemName),
+        new anchor.BN(0).toArrayLike(Buffer, "le", 8),
       ],
       program.programId
     );
@@ -126,34 +217,152 @@ describe("marketplace", () => {
       ],
       program.programId
     );
+
+    [offerPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("offer"),
+        buyer.publicKey.toBuffer(),
+        itemMint.toBuffer(),
+      ],
+      program.programId
+    );
+
+    [offerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("offer_escrow"),
+        offerPda.toBuffer(),
+      ],
+      program.programId
+    );
+
+    [configPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+
+    [feeVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("fee_vault"),
+        paymentMint.toBuffer(),
+      ],
+      program.programId
+    );
+
+    [royaltyConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("royalty"),
+        itemMint.toBuffer(),
+      ],
+      program.programId
+    );
   });
 
-  it("Creates a listing", async () => {
+  it("Initializes the marketplace config and fee vault", async () => {
+    await program.methods
+      .initializeConfig(feeBps)
+      .accounts({
+        admin: admin.publicKey,
+        config: configPda,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    const signature = await program.methods
+      .initializeFeeVault()
+      .accounts({
+        admin: admin.publicKey,
+        config: configPda,
+        feeVault: feeVaultPda,
+        paymentMint: paymentMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([admin])
+      .rpc({ commitment: "confirmed" });
+
+    // Verify the new vault was emitted as an event
+    const tx = await provider.connection.getTransaction(signature, { commitment: "confirmed" });
+    const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const events = Array.from(eventParser.parseLogs(tx.meta.logMessages));
+    const initialized = events.find((event) => event.name === "FeeVaultInitialized");
+    assert.equal(initialized.data.feeVault.toBase58(), feeVaultPda.toBase58());
+    assert.equal(initialized.data.paymentMint.toBase58(), paymentMint.toBase58());
+
+    const config = await program.account.marketplaceConfig.fetch(configPda);
+    assert.equal(config.admin.toBase58(), admin.publicKey.toBase58());
+    assert.equal(config.feeBps, feeBps);
+    assert.isFalse(config.paused);
+  });
+
+  it("Creates a royalty config for the item mint", async () => {
+    // The admin doubles as the item's creator in these tests
     await program.methods
+      .createRoyaltyConfig([{ address: admin.publicKey, shareBps: royaltyBps }])
+      .accounts({
+        authority: seller.publicKey,
+        royaltyConfig: royaltyConfigPda,
+        itemMint: itemMint,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    const royaltyConfig = await program.account.royaltyConfig.fetch(royaltyConfigPda);
+    assert.equal(royaltyConfig.creators.length, 1);
+    assert.equal(royaltyConfig.creators[0].address.toBase58(), admin.publicKey.toBase58());
+    assert.equal(royaltyConfig.creators[0].shareBps, royaltyBps);
+  });
+
+  it("Creates a listing", async () => {
+    const signature = await program.methods
       .createListing(
         new anchor.BN(itemPrice),
         new anchor.BN(itemQuantity),
-        itemName
+        itemName,
+        null,
+        null,
+        null,
+        null,
+        itemMetadata
       )
       .accounts({
         seller: seller.publicKey,
+        sellerProfile: sellerProfilePda,
+        config: configPda,
         listing: listingPda,
         sellerTokenAccount: sellerItemAccount,
         escrowTokenAccount: escrowPda,
         itemMint: itemMint,
+        paymentMint: paymentMint,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
         rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
       .signers([seller])
-      .rpc();
+      .rpc({ commitment: "confirmed" });
+
+    // The seller's first listing also creates their profile
+    const tx = await provider.connection.getTransaction(signature, { commitment: "confirmed" });
+    const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const events = Array.from(eventParser.parseLogs(tx.meta.logMessages));
+    const profileCreated = events.find((event) => event.name === "SellerProfileCreated");
+    assert.equal(profileCreated.data.sellerProfile.toBase58(), sellerProfilePda.toBase58());
+    assert.isDefined(events.find((event) => event.name === "ListingCreated"));
 
     // Verify the listing was created
     const listing = await program.account.listing.fetch(listingPda);
+    assert.equal(listing.version, 1);
     assert.equal(listing.seller.toBase58(), seller.publicKey.toBase58());
+    assert.equal(listing.paymentMint.toBase58(), paymentMint.toBase58());
     assert.equal(listing.price.toString(), itemPrice.toString());
     assert.equal(listing.quantity.toString(), itemQuantity.toString());
     assert.equal(listing.name, itemName);
+    assert.equal(listing.metadata.description, itemMetadata.description);
+    assert.equal(listing.metadata.uri, itemMetadata.uri);
+    assert.deepEqual(listing.metadata.category, itemMetadata.category);
+    assert.deepEqual(listing.metadata.tags, itemMetadata.tags);
     assert.isTrue(listing.active);
 
     // Check if escrow has the items
@@ -161,12 +370,161 @@ describe("marketplace", () => {
     assert.equal(escrowBalance.amount.toString(), itemQuantity.toString());
   });
 
+  it("Rejects migrating a listing already on the current version", async () => {
+    const [newListing, newEscrow] = await nextListing();
+    try {
+      await program.methods
+        .migrateListing()
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          legacyListing: listingPda,
+          legacyEscrowAccount: escrowPda,
+          listing: newListing,
+          escrowTokenAccount: newEscrow,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+      assert.fail("migration should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "ListingUpToDate");
+    }
+  });
+
+  it("Rejects a purchase that omits creator accounts", async () => {
+    try {
+      await program.methods
+        .purchase(
+        new anchor.BN(purchaseQuantity),
+        new anchor.BN(itemPrice),
+        new anchor.BN(itemPrice * purchaseQuantity),
+        []
+      )
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: listingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: escrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: null,
+          saleReceipt: null,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer])
+        .rpc();
+      assert.fail("purchase should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "MissingCreatorAccount");
+    }
+  });
+
+  it("Rejects a seller payment account not owned by the seller", async () => {
+    try {
+      await program.methods
+        .purchase(
+          new anchor.BN(purchaseQuantity),
+          new anchor.BN(itemPrice),
+          new anchor.BN(itemPrice * purchaseQuantity),
+          []
+        )
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: listingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: escrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: adminPaymentAccount,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: null,
+          saleReceipt: null,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+      assert.fail("purchase should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidSellerPaymentOwner");
+    }
+  });
+
+  it("Rejects a purchase above the buyer's max total", async () => {
+    try {
+      await program.methods
+        .purchase(
+          new anchor.BN(purchaseQuantity),
+          new anchor.BN(itemPrice),
+          new anchor.BN(itemPrice * purchaseQuantity - 1),
+          []
+        )
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: listingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: escrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: null,
+          saleReceipt: null,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+      assert.fail("purchase should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "PriceMismatch");
+    }
+  });
+
   it("Purchases items from a listing", async () => {
     const beforeBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
     const beforeSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+    const [saleReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("sale_receipt"), listingPda.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
 
     await program.methods
-      .purchase(new anchor.BN(purchaseQuantity))
+      .purchase(
+        new anchor.BN(purchaseQuantity),
+        new anchor.BN(itemPrice),
+        new anchor.BN(itemPrice * purchaseQuantity),
+        []
+      )
       .accounts({
         buyer: buyer.publicKey,
         listing: listingPda,
@@ -175,8 +533,20 @@ describe("marketplace", () => {
         buyerTokenAccount: buyerItemAccount,
         buyerPaymentAccount: buyerPaymentAccount,
         sellerPaymentAccount: sellerPaymentAccount,
+        config: configPda,
+        feeVault: feeVaultPda,
+        royaltyConfig: royaltyConfigPda,
+        buyerReceipt: null,
+        saleReceipt: saleReceiptPda,
+        itemMint: itemMint,
+        paymentMint: paymentMint,
         tokenProgram: TOKEN_PROGRAM_ID,
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
       })
+      .remainingAccounts([
+        { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+      ])
       .signers([buyer])
       .rpc();
 
@@ -184,6 +554,17 @@ describe("marketplace", () => {
     const listing = await program.account.listing.fetch(listingPda);
     assert.equal(listing.quantity.toString(), (itemQuantity - purchaseQuantity).toString());
     assert.isTrue(listing.active);
+    assert.equal(listing.saleCount.toString(), "1");
+    assert.equal(listing.unitsSold.toString(), purchaseQuantity.toString());
+    assert.equal(listing.totalVolume.toString(), (itemPrice * purchaseQuantity).toString());
+
+    // Verify the sale receipt was written
+    const receipt = await program.account.saleReceipt.fetch(saleReceiptPda);
+    assert.equal(receipt.listing.toBase58(), listingPda.toBase58());
+    assert.equal(receipt.buyer.toBase58(), buyer.publicKey.toBase58());
+    assert.equal(receipt.quantity.toString(), purchaseQuantity.toString());
+    assert.equal(receipt.unitPrice.toString(), itemPrice.toString());
+    assert.isAbove(receipt.slot.toNumber(), 0);
 
     // Verify tokens were transferred
     const afterBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
@@ -196,30 +577,118 @@ describe("marketplace", () => {
     
     assert.equal(
       afterSellerPaymentBalance.toString(),
-      beforeSellerPaymentBalance.add(new anchor.BN(itemPrice * purchaseQuantity)).toString()
+      beforeSellerPaymentBalance.add(
+        new anchor.BN(
+          itemPrice * purchaseQuantity
+            - feeOf(itemPrice * purchaseQuantity)
+            - royaltyOf(itemPrice * purchaseQuantity)
+        )
+      ).toString()
     );
   });
 
+  it("Updates a listing's price and quantity", async () => {
+    const newPrice = itemPrice + 20;
+    const remainingQuantity = itemQuantity - purchaseQuantity;
+    const updateAccounts = {
+      seller: seller.publicKey,
+      listing: listingPda,
+      escrowTokenAccount: escrowPda,
+      sellerTokenAccount: sellerItemAccount,
+      itemMint: itemMint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    // Reprice and pull one item back out of escrow
+    await program.methods
+      .updateListing(new anchor.BN(newPrice), new anchor.BN(0), new anchor.BN(1), null)
+      .accounts(updateAccounts)
+      .signers([seller])
+      .rpc();
+
+    let listing = await program.account.listing.fetch(listingPda);
+    assert.equal(listing.price.toString(), newPrice.toString());
+    assert.equal(listing.quantity.toString(), (remainingQuantity - 1).toString());
+
+    // Top the escrow back up
+    await program.methods
+      .updateListing(null, new anchor.BN(1), new anchor.BN(0), null)
+      .accounts(updateAccounts)
+      .signers([seller])
+      .rpc();
+
+    listing = await program.account.listing.fetch(listingPda);
+    assert.equal(listing.quantity.toString(), remainingQuantity.toString());
+    assert.isTrue(listing.active);
+
+    const escrowBalance = await getAccount(provider.connection, escrowPda);
+    assert.equal(escrowBalance.amount.toString(), remainingQuantity.toString());
+  });
+
+  it("Updates a listing's metadata", async () => {
+    const updateAccounts = {
+      seller: seller.publicKey,
+      listing: listingPda,
+      escrowTokenAccount: escrowPda,
+      sellerTokenAccount: sellerItemAccount,
+      itemMint: itemMint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .updateListing(null, new anchor.BN(0), new anchor.BN(0), {
+          ...itemMetadata,
+          tags: ["a", "b", "c", "d", "e", "f"],
+        })
+        .accounts(updateAccounts)
+        .signers([seller])
+        .rpc();
+      assert.fail("update should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "TooManyTags");
+    }
+
+    const restocked = { ...itemMetadata, category: { gaming: {} }, tags: ["restocked"] };
+    await program.methods
+      .updateListing(null, new anchor.BN(0), new anchor.BN(0), restocked)
+      .accounts(updateAccounts)
+      .signers([seller])
+      .rpc();
+
+    const listing = await program.account.listing.fetch(listingPda);
+    assert.deepEqual(listing.metadata.category, restocked.category);
+    assert.deepEqual(listing.metadata.tags, restocked.tags);
+  });
+
   it("Cancels a listing", async () => {
     const beforeSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
     const remainingQuantity = itemQuantity - purchaseQuantity;
 
-    await program.methods
+    const signature = await program.methods
       .cancelListing()
       .accounts({
         seller: seller.publicKey,
         listing: listingPda,
         escrowTokenAccount: escrowPda,
         sellerTokenAccount: sellerItemAccount,
+        itemMint: itemMint,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
       .signers([seller])
-      .rpc();
+      .rpc({ commitment: "confirmed" });
 
-    // Verify the listing was cancelled
-    const listing = await program.account.listing.fetch(listingPda);
-    assert.isFalse(listing.active);
-    assert.equal(listing.quantity.toString(), "0");
+    // Verify the cancellation was emitted as an event
+    const tx = await provider.connection.getTransaction(signature, { commitment: "confirmed" });
+    const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const events = Array.from(eventParser.parseLogs(tx.meta.logMessages));
+    const cancelled = events.find((event) => event.name === "ListingCancelled");
+    assert.equal(cancelled.data.listing.toBase58(), listingPda.toBase58());
+    assert.equal(cancelled.data.quantityReturned.toString(), remainingQuantity.toString());
+
+    // Verify the listing and escrow were closed
+    assert.isNull(await program.account.listing.fetchNullable(listingPda));
+    assert.isNull(await provider.connection.getAccountInfo(escrowPda));
 
     // Verify tokens were returned
     const afterSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
@@ -228,4 +697,1381 @@ describe("marketplace", () => {
       beforeSellerItemBalance.add(new anchor.BN(remainingQuantity)).toString()
     );
   });
+
+  it("Re-lists under the same name with the next nonce", async () => {
+    const [relistPda, relistEscrowPda] = await nextListing();
+
+    await program.methods
+      .createListing(new anchor.BN(itemPrice), new anchor.BN(1), itemName, null, null, null, null, noMetadata)
+      .accounts({
+        seller: seller.publicKey,
+        sellerProfile: sellerProfilePda,
+        config: configPda,
+        listing: relistPda,
+        sellerTokenAccount: sellerItemAccount,
+        escrowTokenAccount: relistEscrowPda,
+        itemMint: itemMint,
+        paymentMint: paymentMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([seller])
+      .rpc();
+
+    const listing = await program.account.listing.fetch(relistPda);
+    assert.equal(listing.nonce.toString(), "1");
+    assert.equal(listing.name, itemName);
+    const profile = await program.account.sellerProfile.fetch(sellerProfilePda);
+    assert.equal(profile.listingNonce.toString(), "2");
+
+    await program.methods
+      .cancelListing()
+      .accounts({
+        seller: seller.publicKey,
+        listing: relistPda,
+        escrowTokenAccount: relistEscrowPda,
+        sellerTokenAccount: sellerItemAccount,
+        itemMint: itemMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([seller])
+      .rpc();
+  });
+
+  it("Creates an offer", async () => {
+    await program.methods
+      .createOffer(
+        new anchor.BN(offerPrice),
+        new anchor.BN(offerQuantity)
+      )
+      .accounts({
+        buyer: buyer.publicKey,
+        offer: offerPda,
+        buyerPaymentAccount: buyerPaymentAccount,
+        escrowPaymentAccount: offerEscrowPda,
+        itemMint: itemMint,
+        paymentMint: paymentMint,
+        config: configPda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([buyer])
+      .rpc();
+
+    // Verify the offer was created
+    const offer = await program.account.offer.fetch(offerPda);
+    assert.equal(offer.buyer.toBase58(), buyer.publicKey.toBase58());
+    assert.equal(offer.itemMint.toBase58(), itemMint.toBase58());
+    assert.equal(offer.price.toString(), offerPrice.toString());
+    assert.equal(offer.quantity.toString(), offerQuantity.toString());
+    assert.isTrue(offer.active);
+
+    // Check if escrow holds the payment
+    const escrowBalance = await getAccount(provider.connection, offerEscrowPda);
+    assert.equal(escrowBalance.amount.toString(), (offerPrice * offerQuantity).toString());
+  });
+
+  it("Partially fills an offer", async () => {
+    const beforeBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+    const beforeSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+
+    await program.methods
+      .acceptOffer(new anchor.BN(acceptQuantity))
+      .accounts({
+        seller: seller.publicKey,
+        buyer: buyer.publicKey,
+        offer: offerPda,
+        escrowPaymentAccount: offerEscrowPda,
+        sellerTokenAccount: sellerItemAccount,
+        buyerTokenAccount: buyerItemAccount,
+        sellerPaymentAccount: sellerPaymentAccount,
+        buyerPaymentAccount: buyerPaymentAccount,
+        config: configPda,
+        feeVault: feeVaultPda,
+        royaltyConfig: royaltyConfigPda,
+        itemMint: itemMint,
+        paymentMint: paymentMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+      ])
+      .signers([seller])
+      .rpc();
+
+    // Verify the offer was updated
+    const offer = await program.account.offer.fetch(offerPda);
+    assert.equal(offer.quantity.toString(), (offerQuantity - acceptQuantity).toString());
+    assert.isTrue(offer.active);
+
+    // Verify tokens were transferred
+    const afterBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+    const afterSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+
+    assert.equal(
+      (afterBuyerItemBalance - beforeBuyerItemBalance).toString(),
+      acceptQuantity.toString()
+    );
+    assert.equal(
+      (afterSellerPaymentBalance - beforeSellerPaymentBalance).toString(),
+      (
+        offerPrice * acceptQuantity
+          - feeOf(offerPrice * acceptQuantity)
+          - royaltyOf(offerPrice * acceptQuantity)
+      ).toString()
+    );
+  });
+
+  it("Cancels an offer", async () => {
+    const beforeBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+
+    await program.methods
+      .cancelOffer()
+      .accounts({
+        buyer: buyer.publicKey,
+        offer: offerPda,
+        escrowPaymentAccount: offerEscrowPda,
+        buyerPaymentAccount: buyerPaymentAccount,
+        paymentMint: paymentMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([buyer])
+      .rpc();
+
+    // Verify the offer and its escrow were closed
+    assert.isNull(await provider.connection.getAccountInfo(offerPda));
+    assert.isNull(await provider.connection.getAccountInfo(offerEscrowPda));
+
+    // Verify the remaining payment was refunded
+    const afterBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+    assert.equal(
+      (afterBuyerPaymentBalance - beforeBuyerPaymentBalance).toString(),
+      (offerPrice * (offerQuantity - acceptQuantity)).toString()
+    );
+  });
+
+  it("Withdraws accumulated fees", async () => {
+    const expectedFees =
+      feeOf(itemPrice * purchaseQuantity) + feeOf(offerPrice * acceptQuantity);
+    assert.equal(
+      (await getAccount(provider.connection, feeVaultPda)).amount.toString(),
+      expectedFees.toString()
+    );
+    const beforeAdminBalance = (await getAccount(provider.connection, adminPaymentAccount)).amount;
+
+    await program.methods
+      .withdrawFees()
+      .accounts({
+        admin: admin.publicKey,
+        config: configPda,
+        feeVault: feeVaultPda,
+        adminTokenAccount: adminPaymentAccount,
+        paymentMint: paymentMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
+
+    assert.equal((await getAccount(provider.connection, feeVaultPda)).amount.toString(), "0");
+    const afterAdminBalance = (await getAccount(provider.connection, adminPaymentAccount)).amount;
+    assert.equal((afterAdminBalance - beforeAdminBalance).toString(), expectedFees.toString());
+  });
+
+  describe("auctions", () => {
+    const auctionName = "Test Auction";
+    const reservePrice = 100;
+    const minBidIncrement = 10;
+    let auctionPda: anchor.web3.PublicKey;
+    let auctionEscrowPda: anchor.web3.PublicKey;
+    let bidEscrowPda: anchor.web3.PublicKey;
+
+    // Auction and escrow PDAs for the seller's next auction nonce
+    const nextAuction = async () => {
+      const profile = await program.account.sellerProfile.fetchNullable(sellerProfilePda);
+      const nonce = profile ? profile.auctionNonce : new anchor.BN(0);
+      const [auction] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("auction"), seller.publicKey.toBuffer(), nonce.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [escrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), auction.toBuffer()],
+        program.programId
+      );
+      const [bidEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("bid_escrow"), auction.toBuffer()],
+        program.programId
+      );
+      return [auction, escrow, bidEscrow];
+    };
+
+    const createAuction = async (name: string, durationSecs: number) => {
+      const [auction, escrow, bidEscrow] = await nextAuction();
+      const now = Math.floor(Date.now() / 1000);
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 1);
+
+      await program.methods
+        .createAuction(
+          new anchor.BN(reservePrice),
+          new anchor.BN(minBidIncrement),
+          new anchor.BN(now - 1),
+          new anchor.BN(now + durationSecs),
+          new anchor.BN(1),
+          name
+        )
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          auction: auction,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: escrow,
+          bidEscrowAccount: bidEscrow,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      return [auction, escrow, bidEscrow];
+    };
+
+    it("Creates an auction", async () => {
+      [auctionPda, auctionEscrowPda, bidEscrowPda] = await createAuction(auctionName, 5);
+
+      const auction = await program.account.auction.fetch(auctionPda);
+      assert.equal(auction.seller.toBase58(), seller.publicKey.toBase58());
+      assert.equal(auction.reservePrice.toString(), reservePrice.toString());
+      assert.equal(auction.highestBid.toString(), "0");
+      assert.isTrue(auction.active);
+
+      const escrowBalance = await getAccount(provider.connection, auctionEscrowPda);
+      assert.equal(escrowBalance.amount.toString(), "1");
+    });
+
+    it("Rejects a bid below the reserve price", async () => {
+      try {
+        await program.methods
+          .placeBid(new anchor.BN(reservePrice - 1))
+          .accounts({
+            bidder: buyer.publicKey,
+            auction: auctionPda,
+            bidEscrowAccount: bidEscrowPda,
+            bidderPaymentAccount: buyerPaymentAccount,
+            previousBidderPaymentAccount: null,
+            paymentMint: paymentMint,
+            config: configPda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("bid should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "BidTooLow");
+      }
+    });
+
+    it("Refunds the outbid bidder atomically", async () => {
+      await program.methods
+        .placeBid(new anchor.BN(reservePrice))
+        .accounts({
+          bidder: buyer.publicKey,
+          auction: auctionPda,
+          bidEscrowAccount: bidEscrowPda,
+          bidderPaymentAccount: buyerPaymentAccount,
+          previousBidderPaymentAccount: null,
+          paymentMint: paymentMint,
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+
+      const beforeBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+      const winningBid = reservePrice + minBidIncrement;
+
+      await program.methods
+        .placeBid(new anchor.BN(winningBid))
+        .accounts({
+          bidder: bidder.publicKey,
+          auction: auctionPda,
+          bidEscrowAccount: bidEscrowPda,
+          bidderPaymentAccount: bidderPaymentAccount,
+          previousBidderPaymentAccount: buyerPaymentAccount,
+          paymentMint: paymentMint,
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bidder])
+        .rpc();
+
+      const auction = await program.account.auction.fetch(auctionPda);
+      assert.equal(auction.highestBidder.toBase58(), bidder.publicKey.toBase58());
+      assert.equal(auction.highestBid.toString(), winningBid.toString());
+
+      const afterBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+      assert.equal(
+        (afterBuyerPaymentBalance - beforeBuyerPaymentBalance).toString(),
+        reservePrice.toString()
+      );
+      assert.equal(
+        (await getAccount(provider.connection, bidEscrowPda)).amount.toString(),
+        winningBid.toString()
+      );
+    });
+
+    it("Settles the auction after it ends", async () => {
+      await new Promise((resolve) => setTimeout(resolve, 6000));
+
+      const winningBid = reservePrice + minBidIncrement;
+      const beforeSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+
+      await program.methods
+        .settleAuction()
+        .accounts({
+          seller: seller.publicKey,
+          auction: auctionPda,
+          escrowTokenAccount: auctionEscrowPda,
+          bidEscrowAccount: bidEscrowPda,
+          winnerTokenAccount: bidderItemAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .rpc();
+
+      // The auction and both escrows are closed to the seller
+      assert.isNull(await provider.connection.getAccountInfo(auctionPda));
+      assert.isNull(await provider.connection.getAccountInfo(auctionEscrowPda));
+      assert.isNull(await provider.connection.getAccountInfo(bidEscrowPda));
+
+      assert.equal((await getAccount(provider.connection, bidderItemAccount)).amount.toString(), "1");
+
+      const afterSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+      assert.equal(
+        (afterSellerPaymentBalance - beforeSellerPaymentBalance).toString(),
+        (winningBid - feeOf(winningBid) - royaltyOf(winningBid)).toString()
+      );
+    });
+
+    it("Cancels an auction without bids", async () => {
+      const [auction, escrow, bidEscrow] = await createAuction("Unsold Auction", 60);
+      const beforeSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
+
+      await program.methods
+        .cancelAuction()
+        .accounts({
+          seller: seller.publicKey,
+          auction: auction,
+          escrowTokenAccount: escrow,
+          bidEscrowAccount: bidEscrow,
+          sellerTokenAccount: sellerItemAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      assert.isNull(await provider.connection.getAccountInfo(auction));
+      assert.isNull(await provider.connection.getAccountInfo(escrow));
+      assert.isNull(await provider.connection.getAccountInfo(bidEscrow));
+
+      const afterSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
+      assert.equal((afterSellerItemBalance - beforeSellerItemBalance).toString(), "1");
+    });
+  });
+
+  describe("dutch auctions", () => {
+    const dutchName = "Dutch Item";
+    const startPrice = 100;
+    const floorPrice = 50;
+    const duration = 100;
+    let dutchListingPda: anchor.web3.PublicKey;
+    let dutchEscrowPda: anchor.web3.PublicKey;
+
+    it("Creates a Dutch auction listing", async () => {
+      [dutchListingPda, dutchEscrowPda] = await nextListing();
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 1);
+
+      // Start halfway through the decay so the price is well below the start price
+      const startTime = Math.floor(Date.now() / 1000) - duration / 2;
+
+      await program.methods
+        .createListing(
+          new anchor.BN(startPrice),
+          new anchor.BN(1),
+          dutchName,
+          {
+            floorPrice: new anchor.BN(floorPrice),
+            startTime: new anchor.BN(startTime),
+            duration: new anchor.BN(duration),
+          },
+          null,
+          null,
+          null,
+          noMetadata
+        )
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: dutchListingPda,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: dutchEscrowPda,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const listing = await program.account.listing.fetch(dutchListingPda);
+      assert.equal(listing.dutchAuction.floorPrice.toString(), floorPrice.toString());
+      assert.equal(listing.dutchAuction.duration.toString(), duration.toString());
+    });
+
+    it("Rejects a purchase above the buyer's max price", async () => {
+      try {
+        await program.methods
+          .purchase(new anchor.BN(1), new anchor.BN(floorPrice), new anchor.BN(startPrice), [])
+          .accounts({
+            buyer: buyer.publicKey,
+            listing: dutchListingPda,
+            seller: seller.publicKey,
+            escrowTokenAccount: dutchEscrowPda,
+            buyerTokenAccount: buyerItemAccount,
+            buyerPaymentAccount: buyerPaymentAccount,
+            sellerPaymentAccount: sellerPaymentAccount,
+            config: configPda,
+            feeVault: feeVaultPda,
+            royaltyConfig: royaltyConfigPda,
+            buyerReceipt: null,
+            saleReceipt: null,
+            itemMint: itemMint,
+            paymentMint: paymentMint,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            paymentTokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .remainingAccounts([
+            { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+          ])
+          .signers([buyer])
+          .rpc();
+        assert.fail("purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "PriceMismatch");
+      }
+    });
+
+    it("Purchases at the decayed price", async () => {
+      const beforeBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+
+      await program.methods
+        .purchase(new anchor.BN(1), new anchor.BN(startPrice), new anchor.BN(startPrice), [])
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: dutchListingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: dutchEscrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: null,
+          saleReceipt: null,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+
+      const afterBuyerPaymentBalance = (await getAccount(provider.connection, buyerPaymentAccount)).amount;
+      const paid = Number(beforeBuyerPaymentBalance - afterBuyerPaymentBalance);
+      assert.isAbove(paid, floorPrice);
+      assert.isBelow(paid, startPrice);
+    });
+
+    it("Closes the sold-out listing to reclaim rent", async () => {
+      const listing = await program.account.listing.fetch(dutchListingPda);
+      assert.isFalse(listing.active);
+
+      const beforeSellerLamports = await provider.connection.getBalance(seller.publicKey);
+
+      await program.methods
+        .closeListing()
+        .accounts({
+          seller: seller.publicKey,
+          listing: dutchListingPda,
+          escrowTokenAccount: dutchEscrowPda,
+          sellerTokenAccount: sellerItemAccount,
+          itemMint: itemMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      assert.isNull(await program.account.listing.fetchNullable(dutchListingPda));
+      assert.isNull(await provider.connection.getAccountInfo(dutchEscrowPda));
+
+      const afterSellerLamports = await provider.connection.getBalance(seller.publicKey);
+      assert.isAbove(afterSellerLamports, beforeSellerLamports);
+    });
+  });
+
+  describe("listing expiry", () => {
+    const expiringName = "Expiring Item";
+    let expiringListingPda: anchor.web3.PublicKey;
+    let expiringEscrowPda: anchor.web3.PublicKey;
+
+    it("Creates a listing that expires", async () => {
+      [expiringListingPda, expiringEscrowPda] = await nextListing();
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 1);
+
+      const expiresAt = Math.floor(Date.now() / 1000) + 2;
+
+      await program.methods
+        .createListing(new anchor.BN(itemPrice), new anchor.BN(1), expiringName, null, new anchor.BN(expiresAt), null, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: expiringListingPda,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: expiringEscrowPda,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const listing = await program.account.listing.fetch(expiringListingPda);
+      assert.equal(listing.expiresAt.toString(), expiresAt.toString());
+    });
+
+    it("Rejects a purchase after expiry", async () => {
+      await new Promise((resolve) => setTimeout(resolve, 3000));
+
+      try {
+        await program.methods
+          .purchase(new anchor.BN(1), new anchor.BN(itemPrice), new anchor.BN(itemPrice), [])
+          .accounts({
+            buyer: buyer.publicKey,
+            listing: expiringListingPda,
+            seller: seller.publicKey,
+            escrowTokenAccount: expiringEscrowPda,
+            buyerTokenAccount: buyerItemAccount,
+            buyerPaymentAccount: buyerPaymentAccount,
+            sellerPaymentAccount: sellerPaymentAccount,
+            itemMint: itemMint,
+            paymentMint: paymentMint,
+            config: configPda,
+            feeVault: feeVaultPda,
+            royaltyConfig: royaltyConfigPda,
+            buyerReceipt: null,
+            saleReceipt: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            paymentTokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .remainingAccounts([
+            { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+          ])
+          .signers([buyer])
+          .rpc();
+        assert.fail("purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "ListingExpired");
+      }
+    });
+
+    it("Lets anyone expire the listing and return the items", async () => {
+      const beforeSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
+
+      await program.methods
+        .expireListing()
+        .accounts({
+          cranker: bidder.publicKey,
+          seller: seller.publicKey,
+          listing: expiringListingPda,
+          escrowTokenAccount: expiringEscrowPda,
+          sellerTokenAccount: sellerItemAccount,
+          itemMint: itemMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bidder])
+        .rpc();
+
+      assert.isNull(await program.account.listing.fetchNullable(expiringListingPda));
+      assert.isNull(await provider.connection.getAccountInfo(expiringEscrowPda));
+
+      const afterSellerItemBalance = (await getAccount(provider.connection, sellerItemAccount)).amount;
+      assert.equal((afterSellerItemBalance - beforeSellerItemBalance).toString(), "1");
+    });
+  });
+
+  describe("private listings", () => {
+    const reservedName = "Reserved Item";
+    const allowlistName = "Allowlist Item";
+
+    const createPrivateListing = async (name: string, buyerRestriction: object) => {
+      const [listing, escrow] = await nextListing();
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 1);
+
+      await program.methods
+        .createListing(new anchor.BN(itemPrice), new anchor.BN(1), name, null, null, buyerRestriction, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: listing,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: escrow,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      return [listing, escrow];
+    };
+
+    const purchaseAccounts = (listing: anchor.web3.PublicKey, escrow: anchor.web3.PublicKey) => ({
+      buyer: buyer.publicKey,
+      listing: listing,
+      seller: seller.publicKey,
+      escrowTokenAccount: escrow,
+      buyerTokenAccount: buyerItemAccount,
+      buyerPaymentAccount: buyerPaymentAccount,
+      sellerPaymentAccount: sellerPaymentAccount,
+      itemMint: itemMint,
+      paymentMint: paymentMint,
+      config: configPda,
+      feeVault: feeVaultPda,
+      royaltyConfig: royaltyConfigPda,
+      buyerReceipt: null,
+      saleReceipt: null,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    });
+
+    it("Rejects a buyer other than the reserved buyer", async () => {
+      const [listing, escrow] = await createPrivateListing(reservedName, {
+        reserved: { buyer: bidder.publicKey },
+      });
+
+      try {
+        await program.methods
+          .purchase(new anchor.BN(1), new anchor.BN(itemPrice), new anchor.BN(itemPrice), [])
+          .accounts(purchaseAccounts(listing, escrow))
+          .remainingAccounts([
+            { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+          ])
+          .signers([buyer])
+          .rpc();
+        assert.fail("purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "BuyerNotAllowed");
+      }
+    });
+
+    it("Purchases from an allowlist listing with a Merkle proof", async () => {
+      // Two-leaf tree over the buyer and the bidder, pairs hashed in sorted order
+      const buyerLeaf = Buffer.from(keccak_256(buyer.publicKey.toBuffer()));
+      const bidderLeaf = Buffer.from(keccak_256(bidder.publicKey.toBuffer()));
+      const [left, right] = Buffer.compare(buyerLeaf, bidderLeaf) <= 0
+        ? [buyerLeaf, bidderLeaf]
+        : [bidderLeaf, buyerLeaf];
+      const root = Array.from(keccak_256(Buffer.concat([left, right])));
+
+      const [listing, escrow] = await createPrivateListing(allowlistName, {
+        allowlist: { root },
+      });
+      const beforeBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+
+      await program.methods
+        .purchase(new anchor.BN(1), new anchor.BN(itemPrice), new anchor.BN(itemPrice), [
+          Array.from(bidderLeaf),
+        ])
+        .accounts(purchaseAccounts(listing, escrow))
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+
+      const afterBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+      assert.equal((afterBuyerItemBalance - beforeBuyerItemBalance).toString(), "1");
+    });
+  });
+
+  describe("per-buyer limits", () => {
+    const limitedName = "Limited Drop";
+    const maxPerBuyer = 2;
+    let limitedListingPda: anchor.web3.PublicKey;
+    let limitedEscrowPda: anchor.web3.PublicKey;
+    let buyerReceiptPda: anchor.web3.PublicKey;
+
+    const purchaseLimited = (quantity: number) =>
+      program.methods
+        .purchase(
+          new anchor.BN(quantity),
+          new anchor.BN(itemPrice),
+          new anchor.BN(itemPrice * quantity),
+          []
+        )
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: limitedListingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: limitedEscrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          config: configPda,
+          feeVault: feeVaultPda,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: buyerReceiptPda,
+          saleReceipt: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+
+    it("Creates a listing with a per-buyer cap", async () => {
+      [limitedListingPda, limitedEscrowPda] = await nextListing();
+      [buyerReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("buyer_receipt"), limitedListingPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 3);
+
+      await program.methods
+        .createListing(
+          new anchor.BN(itemPrice),
+          new anchor.BN(3),
+          limitedName,
+          null,
+          null,
+          null,
+          new anchor.BN(maxPerBuyer),
+          noMetadata
+        )
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: limitedListingPda,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: limitedEscrowPda,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const listing = await program.account.listing.fetch(limitedListingPda);
+      assert.equal(listing.maxPerBuyer.toString(), maxPerBuyer.toString());
+    });
+
+    it("Tracks purchases up to the cap on the buyer receipt", async () => {
+      await purchaseLimited(1);
+      await purchaseLimited(1);
+
+      const receipt = await program.account.buyerReceipt.fetch(buyerReceiptPda);
+      assert.equal(receipt.buyer.toBase58(), buyer.publicKey.toBase58());
+      assert.equal(receipt.quantity.toString(), maxPerBuyer.toString());
+    });
+
+    it("Rejects a purchase beyond the cap", async () => {
+      try {
+        await purchaseLimited(1);
+        assert.fail("purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "BuyerLimitExceeded");
+      }
+    });
+  });
+
+  describe("native SOL listings", () => {
+    const solName = "SOL Item";
+    const lamportPrice = anchor.web3.LAMPORTS_PER_SOL / 10;
+    let solListingPda: anchor.web3.PublicKey;
+    let solEscrowPda: anchor.web3.PublicKey;
+
+    it("Creates a listing priced in lamports", async () => {
+      [solListingPda, solEscrowPda] = await nextListing();
+
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 1);
+
+      await program.methods
+        .createListing(new anchor.BN(lamportPrice), new anchor.BN(1), solName, null, null, null, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: solListingPda,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: solEscrowPda,
+          itemMint: itemMint,
+          paymentMint: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const listing = await program.account.listing.fetch(solListingPda);
+      assert.deepEqual(listing.paymentKind, { native: {} });
+    });
+
+    it("Purchases with lamports", async () => {
+      const beforeSellerLamports = await provider.connection.getBalance(seller.publicKey);
+      const beforeCreatorLamports = await provider.connection.getBalance(admin.publicKey);
+
+      await program.methods
+        .purchase(new anchor.BN(1), new anchor.BN(lamportPrice), new anchor.BN(lamportPrice), [])
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: solListingPda,
+          seller: seller.publicKey,
+          escrowTokenAccount: solEscrowPda,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: null,
+          sellerPaymentAccount: null,
+          config: configPda,
+          feeVault: null,
+          royaltyConfig: royaltyConfigPda,
+          buyerReceipt: null,
+          saleReceipt: null,
+          itemMint: itemMint,
+          paymentMint: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: null,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: admin.publicKey, isWritable: true, isSigner: false },
+        ])
+        .signers([buyer])
+        .rpc();
+
+      const afterSellerLamports = await provider.connection.getBalance(seller.publicKey);
+      const afterCreatorLamports = await provider.connection.getBalance(admin.publicKey);
+      assert.equal(
+        afterSellerLamports - beforeSellerLamports,
+        lamportPrice - feeOf(lamportPrice) - royaltyOf(lamportPrice)
+      );
+      assert.equal(afterCreatorLamports - beforeCreatorLamports, royaltyOf(lamportPrice));
+    });
+
+    it("Withdraws native fees", async () => {
+      const beforeAdminLamports = await provider.connection.getBalance(admin.publicKey);
+
+      await program.methods
+        .withdrawNativeFees()
+        .accounts({
+          admin: admin.publicKey,
+          config: configPda,
+        })
+        .signers([admin])
+        .rpc();
+
+      const afterAdminLamports = await provider.connection.getBalance(admin.publicKey);
+      // The admin also pays the transaction fee
+      assert.isAbove(afterAdminLamports, beforeAdminLamports);
+    });
+  });
+
+  describe("Token-2022 items", () => {
+    const feeItemName = "Fee Item";
+    const feeItemQuantity = 1_000;
+    const transferFeeBps = 100;
+    let feeItemMint: anchor.web3.PublicKey;
+    let sellerFeeItemAccount: anchor.web3.PublicKey;
+    let feeListingPda: anchor.web3.PublicKey;
+    let feeEscrowPda: anchor.web3.PublicKey;
+
+    before(async () => {
+      // Item mint with a 1% transfer fee
+      const mintKeypair = anchor.web3.Keypair.generate();
+      feeItemMint = mintKeypair.publicKey;
+      const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+      const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+
+      await anchor.web3.sendAndConfirmTransaction(
+        provider.connection,
+        new anchor.web3.Transaction().add(
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: seller.publicKey,
+            newAccountPubkey: feeItemMint,
+            space: mintLen,
+            lamports,
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeTransferFeeConfigInstruction(
+            feeItemMint,
+            seller.publicKey,
+            seller.publicKey,
+            transferFeeBps,
+            BigInt(feeItemQuantity),
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(feeItemMint, 0, seller.publicKey, null, TOKEN_2022_PROGRAM_ID)
+        ),
+        [seller, mintKeypair]
+      );
+
+      sellerFeeItemAccount = await createAccount(
+        provider.connection,
+        seller,
+        feeItemMint,
+        seller.publicKey,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await mintTo(
+        provider.connection,
+        seller,
+        feeItemMint,
+        sellerFeeItemAccount,
+        seller.publicKey,
+        feeItemQuantity,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      [feeListingPda, feeEscrowPda] = await nextListing();
+    });
+
+    it("Lists only the quantity that arrives after the transfer fee", async () => {
+      await program.methods
+        .createListing(new anchor.BN(itemPrice), new anchor.BN(feeItemQuantity), feeItemName, null, null, null, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: feeListingPda,
+          sellerTokenAccount: sellerFeeItemAccount,
+          escrowTokenAccount: feeEscrowPda,
+          itemMint: feeItemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const expected = feeItemQuantity - (feeItemQuantity * transferFeeBps) / 10_000;
+      const listing = await program.account.listing.fetch(feeListingPda);
+      assert.equal(listing.quantity.toString(), expected.toString());
+
+      const escrow = await getAccount(provider.connection, feeEscrowPda, undefined, TOKEN_2022_PROGRAM_ID);
+      assert.equal(escrow.amount.toString(), expected.toString());
+    });
+
+    it("Cancels a Token-2022 listing", async () => {
+      await program.methods
+        .cancelListing()
+        .accounts({
+          seller: seller.publicKey,
+          listing: feeListingPda,
+          escrowTokenAccount: feeEscrowPda,
+          sellerTokenAccount: sellerFeeItemAccount,
+          itemMint: feeItemMint,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      assert.isNull(await program.account.listing.fetchNullable(feeListingPda));
+      assert.isNull(await provider.connection.getAccountInfo(feeEscrowPda));
+    });
+  });
+
+  describe("batch purchases", () => {
+    let cart: [anchor.web3.PublicKey, anchor.web3.PublicKey][] = [];
+
+    // Listing, seller, escrow, buyer items, item mint, royalty config, buyer
+    // payment, seller payment, payment mint, fee vault, buyer receipt (absent
+    // without a per-buyer cap), token programs, then the creator
+    const batchAccounts = (listing: anchor.web3.PublicKey, escrow: anchor.web3.PublicKey) => [
+      { pubkey: listing, isWritable: true, isSigner: false },
+      { pubkey: seller.publicKey, isWritable: true, isSigner: false },
+      { pubkey: escrow, isWritable: true, isSigner: false },
+      { pubkey: buyerItemAccount, isWritable: true, isSigner: false },
+      { pubkey: itemMint, isWritable: false, isSigner: false },
+      { pubkey: royaltyConfigPda, isWritable: false, isSigner: false },
+      { pubkey: buyerPaymentAccount, isWritable: true, isSigner: false },
+      { pubkey: sellerPaymentAccount, isWritable: true, isSigner: false },
+      { pubkey: paymentMint, isWritable: false, isSigner: false },
+      { pubkey: feeVaultPda, isWritable: true, isSigner: false },
+      { pubkey: program.programId, isWritable: false, isSigner: false },
+      { pubkey: TOKEN_PROGRAM_ID, isWritable: false, isSigner: false },
+      { pubkey: TOKEN_PROGRAM_ID, isWritable: false, isSigner: false },
+      { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+    ];
+    const groupLength = 14;
+
+    const purchaseBatch = (items: { quantity: number; maxPrice: number }[]) =>
+      program.methods
+        .purchaseBatch(
+          items.map((item, i) => ({
+            listingIndex: i * groupLength,
+            quantity: new anchor.BN(item.quantity),
+            maxPrice: new anchor.BN(item.maxPrice),
+          }))
+        )
+        .accounts({
+          buyer: buyer.publicKey,
+          config: configPda,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(cart.flatMap(([listing, escrow]) => batchAccounts(listing, escrow)))
+        .signers([buyer]);
+
+    it("Creates two listings for the cart", async () => {
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 2 * itemQuantity);
+
+      for (let i = 0; i < 2; i++) {
+        const [listing, escrow] = await nextListing();
+        await program.methods
+          .createListing(new anchor.BN(itemPrice), new anchor.BN(itemQuantity), `Cart Item ${i}`, null, null, null, null, noMetadata)
+          .accounts({
+            seller: seller.publicKey,
+            sellerProfile: sellerProfilePda,
+            config: configPda,
+            listing,
+            sellerTokenAccount: sellerItemAccount,
+            escrowTokenAccount: escrow,
+            itemMint: itemMint,
+            paymentMint: paymentMint,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .signers([seller])
+          .rpc();
+        cart.push([listing, escrow]);
+      }
+    });
+
+    it("Rolls back the whole cart when one purchase fails", async () => {
+      try {
+        await purchaseBatch([
+          { quantity: 1, maxPrice: itemPrice },
+          { quantity: 1, maxPrice: itemPrice - 1 },
+        ]).rpc();
+        assert.fail("batch purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "PriceMismatch");
+      }
+
+      const first = await program.account.listing.fetch(cart[0][0]);
+      assert.equal(first.quantity.toString(), itemQuantity.toString());
+    });
+
+    it("Purchases from both listings in one instruction", async () => {
+      const beforeBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+
+      await purchaseBatch([
+        { quantity: 1, maxPrice: itemPrice },
+        { quantity: itemQuantity, maxPrice: itemPrice },
+      ]).rpc();
+
+      const afterBuyerItemBalance = (await getAccount(provider.connection, buyerItemAccount)).amount;
+      assert.equal(
+        afterBuyerItemBalance.toString(),
+        beforeBuyerItemBalance.add(new anchor.BN(1 + itemQuantity)).toString()
+      );
+
+      const first = await program.account.listing.fetch(cart[0][0]);
+      assert.equal(first.quantity.toString(), (itemQuantity - 1).toString());
+      const second = await program.account.listing.fetch(cart[1][0]);
+      assert.equal(second.quantity.toString(), "0");
+      assert.isFalse(second.active);
+    });
+  });
+
+  describe("swap listings", () => {
+    let swapListing: anchor.web3.PublicKey;
+    let swapEscrow: anchor.web3.PublicKey;
+
+    it("Lists items priced in another item token", async () => {
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, itemQuantity);
+
+      [swapListing, swapEscrow] = await nextListing();
+      await program.methods
+        .createSwapListing(new anchor.BN(itemPrice), new anchor.BN(itemQuantity), "Swap Item", null, null, null, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: swapListing,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: swapEscrow,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+      const listing = await program.account.listing.fetch(swapListing);
+      assert.deepEqual(listing.paymentKind, { swap: {} });
+      assert.equal(listing.paymentMint.toString(), paymentMint.toString());
+    });
+
+    it("Swaps the buyer's tokens for items without fees", async () => {
+      const beforeSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+      const beforeFeeVaultBalance = (await getAccount(provider.connection, feeVaultPda)).amount;
+      const total = 2 * itemPrice;
+      const [saleReceipt] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("sale_receipt"), swapListing.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      await program.methods
+        .swap(new anchor.BN(2), new anchor.BN(itemPrice), [])
+        .accounts({
+          buyer: buyer.publicKey,
+          listing: swapListing,
+          escrowTokenAccount: swapEscrow,
+          buyerTokenAccount: buyerItemAccount,
+          buyerPaymentAccount: buyerPaymentAccount,
+          sellerPaymentAccount: sellerPaymentAccount,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          config: configPda,
+          buyerReceipt: null,
+          saleReceipt: saleReceipt,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer])
+        .rpc();
+
+      const afterSellerPaymentBalance = (await getAccount(provider.connection, sellerPaymentAccount)).amount;
+      assert.equal(
+        afterSellerPaymentBalance.toString(),
+        beforeSellerPaymentBalance.add(new anchor.BN(total)).toString()
+      );
+      const afterFeeVaultBalance = (await getAccount(provider.connection, feeVaultPda)).amount;
+      assert.equal(afterFeeVaultBalance.toString(), beforeFeeVaultBalance.toString());
+
+      const receipt = await program.account.saleReceipt.fetch(saleReceipt);
+      assert.equal(receipt.buyer.toBase58(), buyer.publicKey.toBase58());
+      assert.equal(receipt.quantity.toString(), "2");
+
+      const listing = await program.account.listing.fetch(swapListing);
+      assert.equal(listing.quantity.toString(), (itemQuantity - 2).toString());
+      assert.equal(listing.unitsSold.toString(), "2");
+    });
+  });
+
+  describe("pause switch", () => {
+    const pausedName = "Paused Item";
+    let pausedListingPda: anchor.web3.PublicKey;
+    let pausedEscrowPda: anchor.web3.PublicKey;
+
+    const setPaused = (paused: boolean) =>
+      program.methods
+        .setPaused(paused)
+        .accounts({
+          admin: admin.publicKey,
+          config: configPda,
+        })
+        .signers([admin])
+        .rpc();
+
+    const createPausedListing = (listing: anchor.web3.PublicKey, escrow: anchor.web3.PublicKey) =>
+      program.methods
+        .createListing(new anchor.BN(itemPrice), new anchor.BN(1), pausedName, null, null, null, null, noMetadata)
+        .accounts({
+          seller: seller.publicKey,
+          sellerProfile: sellerProfilePda,
+          config: configPda,
+          listing: listing,
+          sellerTokenAccount: sellerItemAccount,
+          escrowTokenAccount: escrow,
+          itemMint: itemMint,
+          paymentMint: paymentMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([seller])
+        .rpc();
+
+    before(async () => {
+      await mintTo(provider.connection, seller, itemMint, sellerItemAccount, seller.publicKey, 2);
+
+      [pausedListingPda, pausedEscrowPda] = await nextListing();
+      await createPausedListing(pausedListingPda, pausedEscrowPda);
+    });
+
+    it("Rejects pausing from a non-admin", async () => {
+      try {
+        await program.methods
+          .setPaused(true)
+          .accounts({
+            admin: seller.publicKey,
+            config: configPda,
+          })
+          .signers([seller])
+          .rpc();
+        assert.fail("set_paused should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidAdmin");
+      }
+    });
+
+    it("Pauses the marketplace", async () => {
+      await setPaused(true);
+
+      const config = await program.account.marketplaceConfig.fetch(configPda);
+      assert.isTrue(config.paused);
+    });
+
+    it("Rejects new listings while paused", async () => {
+      const [listing, escrow] = await nextListing();
+      try {
+        await createPausedListing(listing, escrow);
+        assert.fail("create_listing should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "MarketplacePaused");
+      }
+    });
+
+    it("Rejects purchases while paused", async () => {
+      try {
+        await program.methods
+          .purchase(new anchor.BN(1), new anchor.BN(itemPrice), new anchor.BN(itemPrice), [])
+          .accounts({
+            buyer: buyer.publicKey,
+            listing: pausedListingPda,
+            seller: seller.publicKey,
+            escrowTokenAccount: pausedEscrowPda,
+            buyerTokenAccount: buyerItemAccount,
+            buyerPaymentAccount: buyerPaymentAccount,
+            sellerPaymentAccount: sellerPaymentAccount,
+            itemMint: itemMint,
+            paymentMint: paymentMint,
+            config: configPda,
+            feeVault: feeVaultPda,
+            royaltyConfig: royaltyConfigPda,
+            buyerReceipt: null,
+            saleReceipt: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            paymentTokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .remainingAccounts([
+            { pubkey: adminPaymentAccount, isWritable: true, isSigner: false },
+          ])
+          .signers([buyer])
+          .rpc();
+        assert.fail("purchase should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "MarketplacePaused");
+      }
+    });
+
+    it("Still lets sellers cancel while paused", async () => {
+      await program.methods
+        .cancelListing()
+        .accounts({
+          seller: seller.publicKey,
+          listing: pausedListingPda,
+          escrowTokenAccount: pausedEscrowPda,
+          sellerTokenAccount: sellerItemAccount,
+          itemMint: itemMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      assert.isNull(await program.account.listing.fetchNullable(pausedListingPda));
+    });
+
+    it("Unpauses the marketplace", async () => {
+      await setPaused(false);
+
+      const config = await program.account.marketplaceConfig.fetch(configPda);
+      assert.isFalse(config.paused);
+    });
+
+    it("Rejects migrating a config already on the current layout", async () => {
+      try {
+        await program.methods
+          .migrateConfig()
+          .accounts({
+            admin: admin.publicKey,
+            config: configPda,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([admin])
+          .rpc();
+        assert.fail("migrate_config should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "ConfigUpToDate");
+      }
+    });
+  });
 });